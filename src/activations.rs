@@ -1,31 +1,130 @@
 pub trait ActivationFunc {
     fn compute(&self, val: f32) -> f32;
     fn compute_derivative(&self, val: f32) -> f32;
+    /// Name the activation is saved under. Custom activations must be
+    /// registered under the same name to be loaded again.
+    fn name(&self) -> &str;
 }
 
-pub struct RELU {}
-pub struct Sigmoid {}
-pub struct Softmax {}
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Activation {
+    Linear,
+    Relu,
+    Sigmoid,
+    Tanh,
+    Softmax,
+}
+
+impl Activation {
+    /// The function applied to each output of a dense layer. Softmax needs the
+    /// whole output vector, so it is added as its own layer on top of a linear
+    /// dense layer.
+    pub fn element_wise(&self) -> Box<dyn ActivationFunc> {
+        match self {
+            Activation::Linear | Activation::Softmax => Box::new(Linear::new()),
+            Activation::Relu => Box::new(Relu::new()),
+            Activation::Sigmoid => Box::new(Sigmoid::new()),
+            Activation::Tanh => Box::new(Tanh::new()),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Linear;
+#[derive(Default)]
+pub struct Relu;
+#[derive(Default)]
+pub struct Sigmoid;
+#[derive(Default)]
+pub struct Tanh;
+
+impl Linear {
+    pub fn new() -> Self {
+        Self
+    }
+}
 
-impl RELU {
+impl Relu {
     pub fn new() -> Self {
-        Self {}
+        Self
     }
 }
 
 impl Sigmoid {
     pub fn new() -> Self {
-        Self {}
+        Self
     }
 }
 
-impl Softmax {
+impl Tanh {
     pub fn new() -> Self {
-        Self {}
+        Self
+    }
+}
+
+/// What `LayerDense::new` accepts as activation: a built-in activation by
+/// value, an `Activation`, or any boxed `ActivationFunc`.
+pub trait IntoActivation {
+    fn into_activation(self) -> Box<dyn ActivationFunc>;
+}
+
+impl IntoActivation for Box<dyn ActivationFunc> {
+    fn into_activation(self) -> Box<dyn ActivationFunc> {
+        self
     }
 }
 
-impl ActivationFunc for RELU {
+impl<A: ActivationFunc + 'static> IntoActivation for Box<A> {
+    fn into_activation(self) -> Box<dyn ActivationFunc> {
+        self
+    }
+}
+
+impl IntoActivation for Activation {
+    fn into_activation(self) -> Box<dyn ActivationFunc> {
+        self.element_wise()
+    }
+}
+
+impl IntoActivation for Linear {
+    fn into_activation(self) -> Box<dyn ActivationFunc> {
+        Box::new(self)
+    }
+}
+
+impl IntoActivation for Relu {
+    fn into_activation(self) -> Box<dyn ActivationFunc> {
+        Box::new(self)
+    }
+}
+
+impl IntoActivation for Sigmoid {
+    fn into_activation(self) -> Box<dyn ActivationFunc> {
+        Box::new(self)
+    }
+}
+
+impl IntoActivation for Tanh {
+    fn into_activation(self) -> Box<dyn ActivationFunc> {
+        Box::new(self)
+    }
+}
+
+impl ActivationFunc for Linear {
+    fn compute(&self, val: f32) -> f32 {
+        val
+    }
+
+    fn compute_derivative(&self, _val: f32) -> f32 {
+        1.
+    }
+
+    fn name(&self) -> &str {
+        "linear"
+    }
+}
+
+impl ActivationFunc for Relu {
     fn compute(&self, val: f32) -> f32 {
         if val > 0. {
             return val;
@@ -39,31 +138,37 @@ impl ActivationFunc for RELU {
         }
         0.
     }
+
+    fn name(&self) -> &str {
+        "relu"
+    }
 }
 
 impl ActivationFunc for Sigmoid {
     fn compute(&self, val: f32) -> f32 {
-        1_f32 / (1_f32 + 2.71828_f32.powf(-val))
+        1_f32 / (1_f32 + (-val).exp())
     }
 
     fn compute_derivative(&self, val: f32) -> f32 {
         val * (1. - val)
     }
+
+    fn name(&self) -> &str {
+        "sigmoid"
+    }
 }
 
-impl ActivationFunc for Softmax {
+impl ActivationFunc for Tanh {
     fn compute(&self, val: f32) -> f32 {
-        if val > 0. {
-            return val;
-        }
-        0.
+        val.tanh()
     }
 
     fn compute_derivative(&self, val: f32) -> f32 {
-        if val > 0. {
-            return 1.;
-        }
-        0.
+        1. - val.powi(2)
+    }
+
+    fn name(&self) -> &str {
+        "tanh"
     }
 }
 
@@ -73,7 +178,7 @@ mod test {
 
     #[test]
     fn relu_compute() {
-        let rel = RELU::new();
+        let rel = Relu::new();
         assert_eq!(rel.compute(-1.), 0.);
         assert_eq!(rel.compute(0.), 0.);
         assert_eq!(rel.compute(1.2), 1.2);
@@ -81,7 +186,7 @@ mod test {
 
     #[test]
     fn relu_compute_derivative() {
-        let rel = RELU::new();
+        let rel = Relu::new();
         assert_eq!(rel.compute_derivative(-1.), 0.);
         assert_eq!(rel.compute_derivative(0.), 0.);
         assert_eq!(rel.compute_derivative(1.3), 1.);
@@ -106,4 +211,22 @@ mod test {
         assert_eq!(sig.compute_derivative(1.), 0.);
         assert_eq!(sig.compute_derivative(2.), -2.);
     }
+
+    #[test]
+    fn linear_compute() {
+        let lin = Linear::new();
+        assert_eq!(lin.compute(-3.5), -3.5);
+        assert_eq!(lin.compute(12.), 12.);
+        assert_eq!(lin.compute_derivative(-3.5), 1.);
+    }
+
+    #[test]
+    fn tanh_compute() {
+        let tanh = Tanh::new();
+        assert_eq!(tanh.compute(0.), 0.);
+        assert!(tanh.compute(100.) > 0.99);
+        assert!(tanh.compute(-100.) < -0.99);
+        assert_eq!(tanh.compute_derivative(0.), 1.);
+        assert_eq!(tanh.compute_derivative(0.5), 0.75);
+    }
 }
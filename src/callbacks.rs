@@ -0,0 +1,500 @@
+use crate::rann::Rann;
+use crate::serialization::ModelError;
+use crate::training::{EpochLog, History};
+use std::path::PathBuf;
+
+/// Whether `fit` should keep going after an epoch.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Control {
+    Continue,
+    Stop,
+}
+
+/// Hooks called by `fit`. Every method has an empty default, so a callback
+/// only implements the ones it needs. `batch` and `epoch` count from zero.
+pub trait Callback {
+    fn on_train_begin(&mut self, _model: &mut Rann) {}
+    fn on_epoch_begin(&mut self, _epoch: usize, _model: &mut Rann) {}
+    fn on_batch_end(&mut self, _batch: usize, _loss: f32, _model: &mut Rann) {}
+
+    /// Called once the epoch has been trained and validated. Returning
+    /// `Control::Stop` ends training after this epoch.
+    fn on_epoch_end(&mut self, _log: &EpochLog, _model: &mut Rann) -> Control {
+        Control::Continue
+    }
+
+    fn on_train_end(&mut self, _history: &History, _model: &mut Rann) {}
+}
+
+/// A value of the epoch log to watch.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Monitor {
+    Loss,
+    ValLoss,
+    Metric(String),
+    ValMetric(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Min,
+    Max,
+}
+
+impl Monitor {
+    pub fn value(&self, log: &EpochLog) -> Option<f32> {
+        match self {
+            Monitor::Loss => Some(log.train.loss),
+            Monitor::ValLoss => log.validation.as_ref().map(|scores| scores.loss),
+            Monitor::Metric(name) => log.train.metric(name),
+            Monitor::ValMetric(name) => log.validation.as_ref()?.metric(name),
+        }
+    }
+}
+
+// Tracks the best monitored value seen so far.
+pub(crate) struct Best {
+    monitor: Monitor,
+    mode: Mode,
+    pub(crate) min_delta: f32,
+    pub(crate) value: Option<f32>,
+}
+
+impl Best {
+    pub(crate) fn new(monitor: Monitor, mode: Mode) -> Self {
+        Self {
+            monitor,
+            mode,
+            min_delta: 0.,
+            value: None,
+        }
+    }
+
+    pub(crate) fn set_monitor(&mut self, monitor: Monitor, mode: Mode) {
+        self.monitor = monitor;
+        self.mode = mode;
+        self.value = None;
+    }
+
+    /// Whether `log` improved on the best value, which it then becomes, or
+    /// `None` if the log doesn't have the monitored value. A NaN value never
+    /// improves.
+    pub(crate) fn update(&mut self, log: &EpochLog) -> Option<bool> {
+        let value = self.monitor.value(log)?;
+        if value.is_nan() {
+            return Some(false);
+        }
+        let improved = match (self.value, self.mode) {
+            (None, _) => true,
+            (Some(best), Mode::Min) => value < best - self.min_delta,
+            (Some(best), Mode::Max) => value > best + self.min_delta,
+        };
+        if improved {
+            self.value = Some(value);
+        }
+        Some(improved)
+    }
+}
+
+/// Stops training once the monitored value, the validation loss unless
+/// changed with `monitor`, hasn't improved for `patience` epochs. Epochs
+/// whose log lacks the monitored value, such as the validation loss when
+/// `fit` has no validation data, are not counted, so training then runs for
+/// all of its epochs.
+pub struct EarlyStopping {
+    best: Best,
+    patience: usize,
+    restore_best_weights: bool,
+    wait: usize,
+    best_epoch: Option<usize>,
+    best_weights: Option<Vec<Vec<f32>>>,
+    stopped_epoch: Option<usize>,
+}
+
+impl EarlyStopping {
+    pub fn new(patience: usize) -> Self {
+        Self {
+            best: Best::new(Monitor::ValLoss, Mode::Min),
+            patience,
+            restore_best_weights: false,
+            wait: 0,
+            best_epoch: None,
+            best_weights: None,
+            stopped_epoch: None,
+        }
+    }
+
+    pub fn monitor(mut self, monitor: Monitor, mode: Mode) -> Self {
+        self.best.set_monitor(monitor, mode);
+        self
+    }
+
+    /// Changes smaller than `min_delta` don't count as an improvement.
+    pub fn with_min_delta(mut self, min_delta: f32) -> Self {
+        self.best.min_delta = min_delta.abs();
+        self
+    }
+
+    /// Puts the weights of the best epoch back into the model when training
+    /// ends.
+    pub fn restore_best_weights(mut self) -> Self {
+        self.restore_best_weights = true;
+        self
+    }
+
+    pub fn best_epoch(&self) -> Option<usize> {
+        self.best_epoch
+    }
+
+    /// The epoch after which training was stopped, if it was.
+    pub fn stopped_epoch(&self) -> Option<usize> {
+        self.stopped_epoch
+    }
+}
+
+impl Callback for EarlyStopping {
+    fn on_train_begin(&mut self, _model: &mut Rann) {
+        self.best.value = None;
+        self.wait = 0;
+        self.best_epoch = None;
+        self.best_weights = None;
+        self.stopped_epoch = None;
+    }
+
+    fn on_epoch_end(&mut self, log: &EpochLog, model: &mut Rann) -> Control {
+        match self.best.update(log) {
+            None => return Control::Continue,
+            Some(true) => {
+                self.wait = 0;
+                self.best_epoch = Some(log.epoch);
+                if self.restore_best_weights {
+                    self.best_weights = Some(model.weights());
+                }
+                return Control::Continue;
+            }
+            Some(false) => {}
+        }
+        self.wait += 1;
+        if self.wait >= self.patience {
+            self.stopped_epoch = Some(log.epoch);
+            return Control::Stop;
+        }
+        Control::Continue
+    }
+
+    fn on_train_end(&mut self, _history: &History, model: &mut Rann) {
+        if let Some(weights) = &self.best_weights {
+            model.set_weights(weights);
+        }
+    }
+}
+
+/// Saves the model with `Rann::save` while it trains. By default only epochs
+/// that improve the validation loss are saved, or the training loss when
+/// `fit` has no validation data; `every` saves on a fixed schedule instead.
+/// A `{epoch}` in the path is replaced by the epoch number, counting from
+/// one.
+pub struct ModelCheckpoint {
+    path: PathBuf,
+    best: Option<Best>,
+    // whether the default validation loss may give way to the training loss
+    fallback: bool,
+    every: usize,
+    saved: Vec<PathBuf>,
+    error: Option<ModelError>,
+}
+
+impl ModelCheckpoint {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            best: Some(Best::new(Monitor::ValLoss, Mode::Min)),
+            fallback: true,
+            every: 1,
+            saved: vec![],
+            error: None,
+        }
+    }
+
+    /// Only saves epochs that improve `monitor`. Training panics on an epoch
+    /// that doesn't report it, rather than never saving.
+    pub fn monitor(mut self, monitor: Monitor, mode: Mode) -> Self {
+        self.best = Some(Best::new(monitor, mode));
+        self.fallback = false;
+        self
+    }
+
+    /// Saves every `epochs` epochs whether the model improved or not.
+    pub fn every(mut self, epochs: usize) -> Self {
+        self.best = None;
+        self.every = epochs.max(1);
+        self
+    }
+
+    /// The files written so far, in order.
+    pub fn saved(&self) -> &[PathBuf] {
+        &self.saved
+    }
+
+    /// Why saving failed. A failed save also stops training.
+    pub fn error(&self) -> Option<&ModelError> {
+        self.error.as_ref()
+    }
+
+    fn path_for(&self, epoch: usize) -> PathBuf {
+        let path = self.path.to_string_lossy();
+        if path.contains("{epoch}") {
+            PathBuf::from(path.replace("{epoch}", &(epoch + 1).to_string()))
+        } else {
+            self.path.clone()
+        }
+    }
+}
+
+impl Callback for ModelCheckpoint {
+    fn on_train_begin(&mut self, _model: &mut Rann) {
+        if let Some(best) = self.best.as_mut() {
+            if self.fallback {
+                best.set_monitor(Monitor::ValLoss, Mode::Min);
+            }
+            best.value = None;
+        }
+        self.saved.clear();
+        self.error = None;
+    }
+
+    fn on_epoch_end(&mut self, log: &EpochLog, model: &mut Rann) -> Control {
+        let save = match self.best.as_mut() {
+            Some(best) => match best.update(log) {
+                Some(improved) => improved,
+                None if self.fallback => {
+                    best.set_monitor(Monitor::Loss, Mode::Min);
+                    best.update(log) == Some(true)
+                }
+                None => panic!(
+                    "ModelCheckpoint monitors {:?}, which epoch {} doesn't report",
+                    best.monitor,
+                    log.epoch + 1
+                ),
+            },
+            None => (log.epoch + 1) % self.every == 0,
+        };
+        if !save {
+            return Control::Continue;
+        }
+        let path = self.path_for(log.epoch);
+        match model.save(&path) {
+            Ok(()) => {
+                self.saved.push(path);
+                Control::Continue
+            }
+            Err(err) => {
+                self.error = Some(err);
+                Control::Stop
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::activations::Activation::*;
+    use crate::testing::temp;
+    use crate::training::{FitConfig, Scores};
+
+    fn log(epoch: usize, val_loss: f32) -> EpochLog {
+        EpochLog {
+            epoch,
+            train: Scores {
+                loss: 1.,
+                metrics: vec![],
+            },
+            validation: Some(Scores {
+                loss: val_loss,
+                metrics: vec![],
+            }),
+            learning_rate: 0.1,
+        }
+    }
+
+    fn model() -> Rann {
+        Rann::with_activations(&[(2, Linear), (2, Sigmoid)]).with_seed(0)
+    }
+
+    #[test]
+    fn early_stopping() {
+        let mut model = model();
+        let mut stopping = EarlyStopping::new(2).restore_best_weights();
+        stopping.on_train_begin(&mut model);
+        let mut best_weights = vec![];
+        for (epoch, val_loss) in [1., 0.8, 0.9, 0.85, 0.95].iter().enumerate() {
+            if epoch == 1 {
+                best_weights = model.weights();
+            }
+            let control = stopping.on_epoch_end(&log(epoch, *val_loss), &mut model);
+            assert_eq!(control == Control::Stop, epoch == 3);
+            model.set_weights(&[vec![epoch as f32; 4], vec![0.; 2]]);
+            if control == Control::Stop {
+                break;
+            }
+        }
+        assert_eq!(stopping.best_epoch(), Some(1));
+        assert_eq!(stopping.stopped_epoch(), Some(3));
+        stopping.on_train_end(&History::default(), &mut model);
+        assert_eq!(model.weights(), best_weights);
+    }
+
+    #[test]
+    fn early_stopping_without_monitored_value() {
+        let mut model = model();
+        let mut stopping = EarlyStopping::new(1);
+        let mut log = log(0, 1.);
+        log.validation = None;
+        for epoch in 0..5 {
+            log.epoch = epoch;
+            assert_eq!(stopping.on_epoch_end(&log, &mut model), Control::Continue);
+        }
+        assert_eq!(stopping.best_epoch(), None);
+
+        let history = model.fit(
+            &(vec![vec![0.1, 0.2]; 4], vec![vec![1., 0.]; 4]),
+            FitConfig {
+                epochs: 6,
+                callbacks: vec![&mut stopping],
+                ..FitConfig::default()
+            },
+        );
+        assert_eq!(history.epochs.len(), 6);
+        assert_eq!(stopping.stopped_epoch(), None);
+    }
+
+    #[test]
+    fn early_stopping_min_delta_and_mode() {
+        let mut model = model();
+        let mut stopping = EarlyStopping::new(0)
+            .monitor(Monitor::ValMetric("accuracy".to_string()), Mode::Max)
+            .with_min_delta(0.05);
+        let mut log = log(0, 1.);
+        for (accuracy, control) in [
+            (0.5, Control::Continue),
+            (0.6, Control::Continue),
+            (0.62, Control::Stop),
+        ]
+        .iter()
+        {
+            log.validation.as_mut().unwrap().metrics = vec![("accuracy".to_string(), *accuracy)];
+            assert_eq!(stopping.on_epoch_end(&log, &mut model), *control);
+        }
+    }
+
+    #[test]
+    fn model_checkpoint() {
+        let pattern = temp("checkpoint_{epoch}.rann");
+        let mut model = model();
+
+        let mut best = ModelCheckpoint::new(&pattern);
+        best.on_train_begin(&mut model);
+        for (epoch, val_loss) in [1., 0.8, 0.9, 0.7].iter().enumerate() {
+            best.on_epoch_end(&log(epoch, *val_loss), &mut model);
+        }
+        let names: Vec<String> = best
+            .saved()
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        let expected: Vec<String> = [1, 2, 4]
+            .iter()
+            .map(|epoch| format!("rann_{}_checkpoint_{}.rann", std::process::id(), epoch))
+            .collect();
+        assert_eq!(names, expected);
+        let mut loaded = Rann::load(&best.saved()[2]).unwrap();
+        assert_eq!(loaded.forward(&[1., 2.]), model.forward(&[1., 2.]));
+        for path in best.saved() {
+            std::fs::remove_file(path).unwrap();
+        }
+
+        let mut every = ModelCheckpoint::new(&pattern).every(2);
+        every.on_train_begin(&mut model);
+        for epoch in 0..5 {
+            every.on_epoch_end(&log(epoch, 1.), &mut model);
+        }
+        assert_eq!(every.saved().len(), 2);
+        for path in every.saved() {
+            std::fs::remove_file(path).unwrap();
+        }
+
+        let mut fallback = ModelCheckpoint::new(&pattern);
+        fallback.on_train_begin(&mut model);
+        for (epoch, loss) in [1., 0.8, 0.9].iter().enumerate() {
+            let mut log = log(epoch, 0.);
+            log.validation = None;
+            log.train.loss = *loss;
+            fallback.on_epoch_end(&log, &mut model);
+        }
+        assert_eq!(fallback.saved().len(), 2);
+        for path in fallback.saved() {
+            std::fs::remove_file(path).unwrap();
+        }
+
+        let mut failing = ModelCheckpoint::new(temp("missing").join("model.rann"));
+        assert_eq!(failing.on_epoch_end(&log(0, 1.), &mut model), Control::Stop);
+        assert!(failing.error().is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "which epoch 1 doesn't report")]
+    fn model_checkpoint_missing_monitor() {
+        let mut model = model();
+        let mut checkpoint = ModelCheckpoint::new(temp("never.rann"))
+            .monitor(Monitor::ValMetric("accuracy".to_string()), Mode::Max);
+        checkpoint.on_train_begin(&mut model);
+        checkpoint.on_epoch_end(&log(0, 1.), &mut model);
+    }
+
+    #[derive(Default)]
+    struct Counter {
+        batches: usize,
+        epochs: usize,
+        ended: bool,
+    }
+
+    impl Callback for Counter {
+        fn on_batch_end(&mut self, _batch: usize, loss: f32, _model: &mut Rann) {
+            assert!(loss.is_finite());
+            self.batches += 1;
+        }
+
+        fn on_epoch_end(&mut self, log: &EpochLog, _model: &mut Rann) -> Control {
+            self.epochs += 1;
+            match log.epoch {
+                2 => Control::Stop,
+                _ => Control::Continue,
+            }
+        }
+
+        fn on_train_end(&mut self, history: &History, _model: &mut Rann) {
+            self.ended = history.epochs.len() == 3;
+        }
+    }
+
+    #[test]
+    fn fit_calls_callbacks() {
+        let inputs = vec![vec![0.1, 0.2]; 10];
+        let targets = vec![vec![1., 0.]; 10];
+        let mut counter = Counter::default();
+        let history = model().fit(
+            &(inputs, targets),
+            FitConfig {
+                epochs: 10,
+                batch_size: 4,
+                callbacks: vec![&mut counter],
+                ..FitConfig::default()
+            },
+        );
+        assert_eq!(history.epochs.len(), 3);
+        assert_eq!(counter.epochs, 3);
+        assert_eq!(counter.batches, 9);
+        assert!(counter.ended);
+    }
+}
@@ -0,0 +1,424 @@
+use crate::data::{DataError, Dataset};
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// A CSV column, by its 0-based position or by its name in the header.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Column {
+    Index(usize),
+    Name(String),
+}
+
+impl From<usize> for Column {
+    fn from(index: usize) -> Self {
+        Column::Index(index)
+    }
+}
+
+impl From<&str> for Column {
+    fn from(name: &str) -> Self {
+        Column::Name(name.to_string())
+    }
+}
+
+/// Samples read from CSV, one per record. Numeric columns become one value
+/// each; categorical columns are one-hot encoded over the values found in
+/// them, in order of first appearance, unless their categories are given
+/// with `CsvDatasetBuilder::categories`.
+///
+/// ```no_run
+/// use rann::data::csv::CsvDataset;
+///
+/// let iris = CsvDataset::builder()
+///     .targets(&["species"])
+///     .categorical(&["species"])
+///     .open("iris.csv")
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct CsvDataset {
+    inputs: Vec<Vec<f32>>,
+    targets: Vec<Vec<f32>>,
+    feature_names: Vec<String>,
+    target_names: Vec<String>,
+    categories: Vec<(String, Vec<String>)>,
+}
+
+impl CsvDataset {
+    pub fn builder() -> CsvDatasetBuilder {
+        CsvDatasetBuilder::new()
+    }
+
+    /// The name of every input value; a categorical column contributes one
+    /// `column=value` name per category.
+    pub fn feature_names(&self) -> &[String] {
+        &self.feature_names
+    }
+
+    pub fn target_names(&self) -> &[String] {
+        &self.target_names
+    }
+
+    /// The categories of a categorical column, in the order of their one-hot
+    /// outputs.
+    pub fn categories(&self, column: &str) -> Option<&[String]> {
+        self.categories
+            .iter()
+            .find(|(name, _)| name == column)
+            .map(|(_, categories)| &categories[..])
+    }
+}
+
+impl Dataset for CsvDataset {
+    fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    fn get(&self, index: usize) -> (Vec<f32>, Vec<f32>) {
+        (self.inputs[index].clone(), self.targets[index].clone())
+    }
+
+    fn targets(&self) -> Vec<Vec<f32>> {
+        self.targets.clone()
+    }
+}
+
+/// Reads a `CsvDataset`. Targets have to be chosen; the features default to
+/// every other column.
+#[derive(Clone, Debug)]
+pub struct CsvDatasetBuilder {
+    features: Option<Vec<Column>>,
+    targets: Vec<Column>,
+    categorical: Vec<Column>,
+    known_categories: Vec<(Column, Vec<String>)>,
+    has_headers: bool,
+    delimiter: u8,
+    quote: u8,
+}
+
+impl CsvDatasetBuilder {
+    pub fn new() -> Self {
+        Self {
+            features: None,
+            targets: vec![],
+            categorical: vec![],
+            known_categories: vec![],
+            has_headers: true,
+            delimiter: b',',
+            quote: b'"',
+        }
+    }
+
+    pub fn features<C: Into<Column> + Clone>(mut self, columns: &[C]) -> Self {
+        self.features = Some(columns.iter().cloned().map(Into::into).collect());
+        self
+    }
+
+    pub fn targets<C: Into<Column> + Clone>(mut self, columns: &[C]) -> Self {
+        self.targets = columns.iter().cloned().map(Into::into).collect();
+        self
+    }
+
+    /// Columns holding categories rather than numbers, features or targets.
+    pub fn categorical<C: Into<Column> + Clone>(mut self, columns: &[C]) -> Self {
+        self.categorical = columns.iter().cloned().map(Into::into).collect();
+        self
+    }
+
+    /// Makes `column` categorical with the given categories, in the order of
+    /// its one-hot outputs, rather than those found in the data. Reading a
+    /// test set with the categories of the training set keeps their encodings
+    /// the same; other values are a `DataError::UnknownCategory`.
+    pub fn categories<C: Into<Column>>(mut self, column: C, categories: &[&str]) -> Self {
+        let column = column.into();
+        self.known_categories.retain(|(known, _)| *known != column);
+        self.known_categories.push((
+            column,
+            categories
+                .iter()
+                .map(|category| category.to_string())
+                .collect(),
+        ));
+        self
+    }
+
+    /// Whether the first record names the columns, true by default.
+    pub fn has_headers(mut self, has_headers: bool) -> Self {
+        self.has_headers = has_headers;
+        self
+    }
+
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn quote(mut self, quote: u8) -> Self {
+        self.quote = quote;
+        self
+    }
+
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> Result<CsvDataset, DataError> {
+        self.from_reader(File::open(path)?)
+    }
+
+    pub fn from_reader<R: io::Read>(&self, reader: R) -> Result<CsvDataset, DataError> {
+        if self.targets.is_empty() {
+            return Err(DataError::Invalid("no target columns chosen".to_string()));
+        }
+        let mut reader = ::csv::ReaderBuilder::new()
+            .has_headers(self.has_headers)
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .from_reader(reader);
+        let headers: Vec<String> = match self.has_headers {
+            true => reader
+                .headers()?
+                .iter()
+                .map(|h| h.trim().to_string())
+                .collect(),
+            false => vec![],
+        };
+        let records = reader
+            .records()
+            .collect::<Result<Vec<::csv::StringRecord>, ::csv::Error>>()?;
+        let width = records.first().map_or(headers.len(), |record| record.len());
+        let names: Vec<String> = (0..width)
+            .map(|i| headers.get(i).cloned().unwrap_or_else(|| i.to_string()))
+            .collect();
+
+        let resolve = |columns: &[Column]| -> Result<Vec<usize>, DataError> {
+            columns
+                .iter()
+                .map(|column| match column {
+                    Column::Index(i) if *i < width => Ok(*i),
+                    Column::Index(i) => Err(DataError::UnknownColumn(i.to_string())),
+                    Column::Name(name) => headers
+                        .iter()
+                        .position(|header| header == name)
+                        .ok_or_else(|| DataError::UnknownColumn(name.clone())),
+                })
+                .collect()
+        };
+        let targets = resolve(&self.targets)?;
+        let features = match &self.features {
+            Some(features) => resolve(features)?,
+            None => (0..width).filter(|i| !targets.contains(i)).collect(),
+        };
+        let categorical = resolve(&self.categorical)?;
+        let mut known = vec![];
+        for (column, categories) in &self.known_categories {
+            known.push((resolve(std::slice::from_ref(column))?[0], categories));
+        }
+
+        let mut categories: Vec<(usize, Vec<String>)> = vec![];
+        for column in features.iter().chain(&targets) {
+            if categories.iter().any(|(c, _)| c == column) {
+                continue;
+            }
+            if let Some((_, given)) = known.iter().find(|(c, _)| c == column) {
+                categories.push((*column, given.to_vec()));
+                continue;
+            }
+            if !categorical.contains(column) {
+                continue;
+            }
+            let mut found: Vec<String> = vec![];
+            for record in &records {
+                let value = record[*column].trim();
+                if !found.iter().any(|category| category == value) {
+                    found.push(value.to_string());
+                }
+            }
+            categories.push((*column, found));
+        }
+        let categories_of = |column: usize| {
+            categories
+                .iter()
+                .find(|(c, _)| *c == column)
+                .map(|(_, found)| found)
+        };
+
+        let column_names = |columns: &[usize]| -> Vec<String> {
+            columns
+                .iter()
+                .flat_map(|column| match categories_of(*column) {
+                    Some(found) => found
+                        .iter()
+                        .map(|category| format!("{}={}", names[*column], category))
+                        .collect(),
+                    None => vec![names[*column].clone()],
+                })
+                .collect()
+        };
+        let values = |record: &::csv::StringRecord,
+                      line: u64,
+                      columns: &[usize]|
+         -> Result<Vec<f32>, DataError> {
+            let mut values = vec![];
+            for column in columns {
+                let value = record[*column].trim();
+                match categories_of(*column) {
+                    Some(found) if !found.iter().any(|c| c == value) => {
+                        return Err(DataError::UnknownCategory {
+                            line,
+                            column: *column,
+                            value: value.to_string(),
+                        })
+                    }
+                    Some(found) => {
+                        values.extend(found.iter().map(|c| if c == value { 1. } else { 0. }))
+                    }
+                    None => values.push(value.parse::<f32>().map_err(|_| DataError::Parse {
+                        line,
+                        column: *column,
+                        value: value.to_string(),
+                    })?),
+                }
+            }
+            Ok(values)
+        };
+
+        let mut dataset = CsvDataset {
+            inputs: vec![],
+            targets: vec![],
+            feature_names: column_names(&features),
+            target_names: column_names(&targets),
+            categories: categories
+                .iter()
+                .map(|(column, found)| (names[*column].clone(), found.clone()))
+                .collect(),
+        };
+        for record in &records {
+            let line = record.position().map_or(0, |position| position.line());
+            dataset.inputs.push(values(record, line, &features)?);
+            dataset.targets.push(values(record, line, &targets)?);
+        }
+        Ok(dataset)
+    }
+}
+
+impl Default for CsvDatasetBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::temp;
+
+    #[test]
+    fn numeric_columns() {
+        let data = "x,y,label\n0.5,1,0\n-2, 3.5 ,1\n";
+        let dataset = CsvDataset::builder()
+            .targets(&["label"])
+            .from_reader(data.as_bytes())
+            .unwrap();
+        assert_eq!(dataset.len(), 2);
+        assert_eq!(dataset.get(1), (vec![-2., 3.5], vec![1.]));
+        assert_eq!(dataset.feature_names(), ["x", "y"]);
+
+        let path = temp("numeric_columns.csv");
+        std::fs::write(&path, data).unwrap();
+        let opened = CsvDataset::builder()
+            .features(&[2])
+            .targets(&[1, 0])
+            .open(&path)
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(opened.get(0), (vec![0.], vec![1., 0.5]));
+        assert_eq!(opened.target_names(), ["y", "x"]);
+    }
+
+    #[test]
+    fn categorical_columns_and_format() {
+        let data = "'size';'colour';'kind'\n1;'red';'a; b'\n2;'blue';'c'\n3;'red';'c'\n";
+        let dataset = CsvDataset::builder()
+            .delimiter(b';')
+            .quote(b'\'')
+            .targets(&["kind"])
+            .categorical(&["colour", "kind"])
+            .from_reader(data.as_bytes())
+            .unwrap();
+        assert_eq!(
+            dataset.feature_names(),
+            ["size", "colour=red", "colour=blue"]
+        );
+        assert_eq!(dataset.categories("kind").unwrap(), ["a; b", "c"]);
+        assert_eq!(dataset.get(0), (vec![1., 1., 0.], vec![1., 0.]));
+        assert_eq!(dataset.get(1), (vec![2., 0., 1.], vec![0., 1.]));
+
+        let dataset = CsvDataset::builder()
+            .has_headers(false)
+            .targets(&[0])
+            .from_reader("1,2\n3,4\n".as_bytes())
+            .unwrap();
+        assert_eq!(dataset.targets(), vec![vec![1.], vec![3.]]);
+        assert_eq!(dataset.feature_names(), ["1"]);
+    }
+
+    #[test]
+    fn known_categories() {
+        let train = "size,colour,kind\n1,red,a\n2,blue,b\n";
+        let train = CsvDataset::builder()
+            .targets(&["kind"])
+            .categorical(&["colour", "kind"])
+            .from_reader(train.as_bytes())
+            .unwrap();
+        let colours = train.categories("colour").unwrap().to_vec();
+        let colours: Vec<&str> = colours.iter().map(|c| c.as_str()).collect();
+
+        let builder = CsvDataset::builder()
+            .targets(&["kind"])
+            .categories("colour", &colours)
+            .categories(2, &["a", "b"]);
+        let test = builder
+            .from_reader("size,colour,kind\n3,blue,a\n".as_bytes())
+            .unwrap();
+        assert_eq!(test.feature_names(), train.feature_names());
+        assert_eq!(test.get(0), (vec![3., 0., 1.], vec![1., 0.]));
+
+        match builder.from_reader("size,colour,kind\n3,blue,a\n4,green,b\n".as_bytes()) {
+            Err(DataError::UnknownCategory {
+                line: 3,
+                column: 1,
+                value,
+            }) => assert_eq!(value, "green"),
+            other => panic!("expected an unknown category, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let builder = CsvDataset::builder().targets(&["b"]);
+        match builder.from_reader("a,b\n1,2\n3,x\n".as_bytes()) {
+            Err(DataError::Parse {
+                line: 3,
+                column: 1,
+                value,
+            }) => assert_eq!(value, "x"),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+        assert!(matches!(
+            builder.from_reader("a,c\n1,2\n".as_bytes()),
+            Err(DataError::UnknownColumn(_))
+        ));
+        assert!(matches!(
+            CsvDataset::builder()
+                .targets(&[2])
+                .from_reader("a,b\n1,2\n".as_bytes()),
+            Err(DataError::UnknownColumn(_))
+        ));
+        assert!(matches!(
+            CsvDataset::builder().from_reader("a,b\n1,2\n".as_bytes()),
+            Err(DataError::Invalid(_))
+        ));
+        assert!(matches!(
+            builder.from_reader("a,b\n1,2\n3\n".as_bytes()),
+            Err(DataError::Csv(_))
+        ));
+    }
+}
@@ -0,0 +1,179 @@
+use crate::data::Dataset;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+/// Inputs and targets of one mini-batch.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Batch {
+    pub inputs: Vec<Vec<f32>>,
+    pub targets: Vec<Vec<f32>>,
+}
+
+impl Batch {
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+}
+
+/// Splits a dataset into mini-batches, in a new random order every epoch
+/// unless shuffling is turned off. Samples are only fetched from the dataset
+/// once their batch is reached.
+pub struct DataLoader<'a, D: Dataset + ?Sized> {
+    dataset: &'a D,
+    batch_size: usize,
+    shuffle: bool,
+    drop_last: bool,
+    rng: ChaCha8Rng,
+}
+
+impl<'a, D: Dataset + ?Sized> DataLoader<'a, D> {
+    pub fn new(dataset: &'a D, batch_size: usize) -> Self {
+        Self {
+            dataset,
+            batch_size: batch_size.max(1),
+            shuffle: true,
+            drop_last: false,
+            rng: ChaCha8Rng::from_rng(rand::thread_rng()).unwrap(),
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+        self
+    }
+
+    pub fn with_shuffle(mut self, shuffle: bool) -> Self {
+        self.shuffle = shuffle;
+        self
+    }
+
+    /// Leaves out the last batch of an epoch if it would be smaller than the
+    /// batch size.
+    pub fn drop_last(mut self) -> Self {
+        self.drop_last = true;
+        self
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    /// The number of batches in an epoch.
+    pub fn len(&self) -> usize {
+        if self.drop_last {
+            self.dataset.len() / self.batch_size
+        } else {
+            self.dataset.len().div_ceil(self.batch_size)
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The batches of the next epoch.
+    pub fn epoch(&mut self) -> Batches<'a, D> {
+        let mut order: Vec<usize> = (0..self.dataset.len()).collect();
+        if self.shuffle {
+            order.shuffle(&mut self.rng);
+        }
+        order.truncate(self.len() * self.batch_size);
+        Batches {
+            dataset: self.dataset,
+            order,
+            batch_size: self.batch_size,
+            next: 0,
+        }
+    }
+}
+
+impl<'a, D: Dataset + ?Sized> IntoIterator for &mut DataLoader<'a, D> {
+    type Item = Batch;
+    type IntoIter = Batches<'a, D>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.epoch()
+    }
+}
+
+/// The batches of one epoch of a `DataLoader`.
+pub struct Batches<'a, D: Dataset + ?Sized> {
+    dataset: &'a D,
+    order: Vec<usize>,
+    batch_size: usize,
+    next: usize,
+}
+
+impl<'a, D: Dataset + ?Sized> Iterator for Batches<'a, D> {
+    type Item = Batch;
+
+    fn next(&mut self) -> Option<Batch> {
+        if self.next >= self.order.len() {
+            return None;
+        }
+        let end = (self.next + self.batch_size).min(self.order.len());
+        let mut batch = Batch::default();
+        for index in &self.order[self.next..end] {
+            let (input, target) = self.dataset.get(*index);
+            batch.inputs.push(input);
+            batch.targets.push(target);
+        }
+        self.next = end;
+        Some(batch)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = (self.order.len() - self.next).div_ceil(self.batch_size);
+        (left, Some(left))
+    }
+}
+
+impl<'a, D: Dataset + ?Sized> ExactSizeIterator for Batches<'a, D> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::Generated;
+
+    fn indices(batch: &Batch) -> Vec<usize> {
+        batch.inputs.iter().map(|input| input[0] as usize).collect()
+    }
+
+    #[test]
+    fn batches() {
+        let data = Generated::new(10, |i| (vec![i as f32], vec![0.]));
+        let mut loader = DataLoader::new(&data, 4).with_shuffle(false);
+        assert_eq!(loader.len(), 3);
+        let batches: Vec<Vec<usize>> = loader.epoch().map(|batch| indices(&batch)).collect();
+        assert_eq!(
+            batches,
+            vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7], vec![8, 9]]
+        );
+
+        let mut loader = DataLoader::new(&data, 4).drop_last().with_seed(3);
+        assert_eq!(loader.len(), 2);
+        let epoch = loader.epoch();
+        assert_eq!(epoch.len(), 2);
+        let mut first: Vec<usize> = epoch.flat_map(|batch| indices(&batch)).collect();
+        assert_eq!(first.len(), 8);
+        let second: Vec<usize> = (&mut loader)
+            .into_iter()
+            .flat_map(|batch| indices(&batch))
+            .collect();
+        assert_ne!(first, second);
+        first.sort_unstable();
+        first.dedup();
+        assert_eq!(first.len(), 8);
+
+        let mut again = DataLoader::new(&data, 4).drop_last().with_seed(3);
+        let replayed: Vec<usize> = again.epoch().flat_map(|batch| indices(&batch)).collect();
+        let mut loader = DataLoader::new(&data, 4).drop_last().with_seed(3);
+        let original: Vec<usize> = loader.epoch().flat_map(|batch| indices(&batch)).collect();
+        assert_eq!(replayed, original);
+    }
+}
@@ -0,0 +1,288 @@
+//! Loaders for the MNIST handwritten digits, either the original IDX files
+//! (`train-images-idx3-ubyte` and `train-labels-idx1-ubyte`, optionally
+//! gzipped) or the CSV variant with the label in the first column.
+
+use crate::data::{DataError, Dataset, VecDataset};
+use flate2::read::GzDecoder;
+use std::fs;
+use std::path::Path;
+
+pub const CLASSES: usize = 10;
+
+/// How labels are turned into targets.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Labels {
+    /// Ten outputs, 1 for the digit and 0 for the rest.
+    OneHot,
+    /// A single output holding the digit.
+    Index,
+}
+
+/// The contents of an IDX file of unsigned bytes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Idx {
+    pub dimensions: Vec<usize>,
+    pub data: Vec<u8>,
+}
+
+/// Reads an IDX file of unsigned bytes, gunzipping it first if it is
+/// compressed.
+pub fn read_idx<P: AsRef<Path>>(path: P) -> Result<Idx, DataError> {
+    let bytes = fs::read(path)?;
+    if bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut decoded = vec![];
+        std::io::Read::read_to_end(&mut GzDecoder::new(&bytes[..]), &mut decoded)?;
+        parse_idx(&decoded)
+    } else {
+        parse_idx(&bytes)
+    }
+}
+
+fn parse_idx(bytes: &[u8]) -> Result<Idx, DataError> {
+    if bytes.len() < 4 || bytes[0] != 0 || bytes[1] != 0 {
+        return Err(DataError::Invalid("not an IDX file".to_string()));
+    }
+    if bytes[2] != 0x08 {
+        return Err(DataError::Invalid(format!(
+            "IDX data type {:#04x} is not unsigned bytes",
+            bytes[2]
+        )));
+    }
+    let header = 4 + 4 * bytes[3] as usize;
+    if bytes.len() < header {
+        return Err(DataError::Invalid("IDX header is truncated".to_string()));
+    }
+    let dimensions: Vec<usize> = bytes[4..header]
+        .chunks(4)
+        .map(|size| u32::from_be_bytes([size[0], size[1], size[2], size[3]]) as usize)
+        .collect();
+    let expected = dimensions
+        .iter()
+        .try_fold(1_usize, |size, dimension| size.checked_mul(*dimension));
+    if expected != Some(bytes.len() - header) {
+        return Err(DataError::Invalid(format!(
+            "IDX dimensions {:?} don't match the {} bytes of data",
+            dimensions,
+            bytes.len() - header
+        )));
+    }
+    Ok(Idx {
+        dimensions,
+        data: bytes[header..].to_vec(),
+    })
+}
+
+/// Loads MNIST with pixels scaled to [0, 1].
+///
+/// ```no_run
+/// use rann::data::mnist::{Labels, MnistLoader};
+///
+/// let train = MnistLoader::new()
+///     .labels(Labels::Index)
+///     .load_idx("mnist/train-images-idx3-ubyte.gz", "mnist/train-labels-idx1-ubyte.gz")
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct MnistLoader {
+    labels: Labels,
+    limit: Option<usize>,
+}
+
+impl MnistLoader {
+    /// One-hot labels and every sample.
+    pub fn new() -> Self {
+        Self {
+            labels: Labels::OneHot,
+            limit: None,
+        }
+    }
+
+    pub fn labels(mut self, labels: Labels) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Keeps only the first `limit` samples.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    fn target(&self, label: u8) -> Result<Vec<f32>, DataError> {
+        if label as usize >= CLASSES {
+            return Err(DataError::Invalid(format!(
+                "label {} is not a digit",
+                label
+            )));
+        }
+        Ok(match self.labels {
+            Labels::OneHot => {
+                let mut target = vec![0.; CLASSES];
+                target[label as usize] = 1.;
+                target
+            }
+            Labels::Index => vec![label as f32],
+        })
+    }
+
+    pub fn load_idx<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        images: P,
+        labels: Q,
+    ) -> Result<VecDataset, DataError> {
+        let images = read_idx(images)?;
+        let labels = read_idx(labels)?;
+        if images.dimensions.len() != 3 || labels.dimensions.len() != 1 {
+            return Err(DataError::Invalid(format!(
+                "expected images of 3 dimensions and labels of 1, found {:?} and {:?}",
+                images.dimensions, labels.dimensions
+            )));
+        }
+        if images.dimensions[0] != labels.dimensions[0] {
+            return Err(DataError::Invalid(format!(
+                "{} images but {} labels",
+                images.dimensions[0], labels.dimensions[0]
+            )));
+        }
+        let pixels = images.dimensions[1] * images.dimensions[2];
+        let count = self
+            .limit
+            .map_or(labels.data.len(), |limit| limit.min(labels.data.len()));
+        let mut dataset = VecDataset::default();
+        for (image, label) in images.data.chunks(pixels.max(1)).zip(&labels.data[..count]) {
+            dataset.push(
+                image.iter().map(|pixel| *pixel as f32 / 255.).collect(),
+                self.target(*label)?,
+            );
+        }
+        Ok(dataset)
+    }
+
+    /// Reads CSV with the label followed by the pixels on every line. A header
+    /// line, if there is one, is skipped.
+    pub fn load_csv<P: AsRef<Path>>(&self, path: P) -> Result<VecDataset, DataError> {
+        let mut reader = ::csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_path(path)?;
+        let mut dataset = VecDataset::default();
+        for record in reader.records() {
+            if self.limit == Some(dataset.len()) {
+                break;
+            }
+            let record = record?;
+            let line = record.position().map_or(0, |position| position.line());
+            let parse = |column: usize, value: &str| {
+                value.trim().parse::<u8>().map_err(|_| DataError::Parse {
+                    line,
+                    column,
+                    value: value.to_string(),
+                })
+            };
+            let label = match record.get(0).map(|value| parse(0, value)) {
+                Some(Ok(label)) => label,
+                Some(Err(_)) if line == 1 => continue,
+                Some(Err(err)) => return Err(err),
+                None => return Err(DataError::Invalid(format!("line {} is empty", line))),
+            };
+            let image = record
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(|(column, value)| parse(column, value).map(|pixel| pixel as f32 / 255.))
+                .collect::<Result<Vec<f32>, DataError>>()?;
+            dataset.push(image, self.target(label)?);
+        }
+        Ok(dataset)
+    }
+}
+
+impl Default for MnistLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::temp;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn idx(dimensions: &[u32], data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0, 0, 0x08, dimensions.len() as u8];
+        for size in dimensions {
+            bytes.extend_from_slice(&size.to_be_bytes());
+        }
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn written(name: &str, bytes: &[u8]) -> std::path::PathBuf {
+        let path = temp(name);
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn load_idx() {
+        let images = written(
+            "images",
+            &idx(&[3, 2, 2], &[0, 255, 51, 0, 1, 2, 3, 4, 5, 6, 7, 8]),
+        );
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(&idx(&[3], &[7, 0, 9])).unwrap();
+        let labels = written("labels.gz", &encoder.finish().unwrap());
+
+        let dataset = MnistLoader::new().load_idx(&images, &labels).unwrap();
+        assert_eq!(dataset.len(), 3);
+        let (image, target) = dataset.get(0);
+        assert_eq!(image, vec![0., 1., 0.2, 0.]);
+        assert_eq!(target, vec![0., 0., 0., 0., 0., 0., 0., 1., 0., 0.]);
+
+        let dataset = MnistLoader::new()
+            .labels(Labels::Index)
+            .limit(2)
+            .load_idx(&images, &labels)
+            .unwrap();
+        assert_eq!(dataset.targets(), vec![vec![7.], vec![0.]]);
+
+        let result = MnistLoader::new().load_idx(&labels, &images);
+        fs::remove_file(&images).unwrap();
+        fs::remove_file(&labels).unwrap();
+        assert!(matches!(result, Err(DataError::Invalid(_))));
+
+        let truncated = written("truncated", &idx(&[3], &[1, 2]));
+        let result = read_idx(&truncated);
+        fs::remove_file(&truncated).unwrap();
+        assert!(matches!(result, Err(DataError::Invalid(_))));
+        let huge = idx(&[u32::MAX; 8], &[]);
+        assert!(matches!(parse_idx(&huge), Err(DataError::Invalid(_))));
+        assert!(matches!(
+            read_idx("/nonexistent/rann/mnist"),
+            Err(DataError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn load_csv() {
+        let csv = written("mnist.csv", b"label,1x1,1x2\n3,0,255\n12,0,0\n");
+        let loader = MnistLoader::new().labels(Labels::Index);
+        let dataset = loader.clone().limit(1).load_csv(&csv).unwrap();
+        assert_eq!(dataset.get(0), (vec![0., 1.], vec![3.]));
+        let result = loader.load_csv(&csv);
+        fs::remove_file(&csv).unwrap();
+        assert!(matches!(result, Err(DataError::Invalid(_))));
+
+        let csv = written("mnist_bad.csv", b"1,2,x\n");
+        let result = MnistLoader::new().load_csv(&csv);
+        fs::remove_file(&csv).unwrap();
+        match result {
+            Err(DataError::Parse {
+                line: 1, column: 2, ..
+            }) => {}
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+}
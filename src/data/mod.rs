@@ -0,0 +1,252 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+pub mod csv;
+pub mod loader;
+pub mod mnist;
+pub mod split;
+
+/// Indexed pairs of input and target vectors that a model can be trained or
+/// evaluated on.
+pub trait Dataset {
+    fn len(&self) -> usize;
+    fn get(&self, index: usize) -> (Vec<f32>, Vec<f32>);
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every target, in order.
+    fn targets(&self) -> Vec<Vec<f32>> {
+        (0..self.len()).map(|i| self.get(i).1).collect()
+    }
+}
+
+// The length of parallel inputs and targets, which must match as in
+// `VecDataset::new`.
+fn paired_len(inputs: &[Vec<f32>], targets: &[Vec<f32>]) -> usize {
+    assert_eq!(
+        inputs.len(),
+        targets.len(),
+        "every input needs exactly one target"
+    );
+    inputs.len()
+}
+
+/// Inputs and targets kept in two parallel vectors. Panics when used if
+/// there aren't as many targets as inputs.
+impl Dataset for (Vec<Vec<f32>>, Vec<Vec<f32>>) {
+    fn len(&self) -> usize {
+        paired_len(&self.0, &self.1)
+    }
+
+    fn get(&self, index: usize) -> (Vec<f32>, Vec<f32>) {
+        (self.0[index].clone(), self.1[index].clone())
+    }
+}
+
+impl Dataset for (&[Vec<f32>], &[Vec<f32>]) {
+    fn len(&self) -> usize {
+        paired_len(self.0, self.1)
+    }
+
+    fn get(&self, index: usize) -> (Vec<f32>, Vec<f32>) {
+        (self.0[index].clone(), self.1[index].clone())
+    }
+}
+
+/// The samples of `dataset` at `indices`, in that order.
+pub struct Subset<'a, D: Dataset + ?Sized> {
+    dataset: &'a D,
+    indices: Vec<usize>,
+}
+
+impl<'a, D: Dataset + ?Sized> Subset<'a, D> {
+    pub fn new(dataset: &'a D, indices: Vec<usize>) -> Self {
+        Self { dataset, indices }
+    }
+
+    /// The indices into the wrapped dataset, in order.
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+}
+
+impl<'a, D: Dataset + ?Sized> Dataset for Subset<'a, D> {
+    fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    fn get(&self, index: usize) -> (Vec<f32>, Vec<f32>) {
+        self.dataset.get(self.indices[index])
+    }
+}
+
+/// Inputs and targets held in memory, one target per input.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VecDataset {
+    inputs: Vec<Vec<f32>>,
+    targets: Vec<Vec<f32>>,
+}
+
+impl VecDataset {
+    /// Panics if there aren't as many targets as inputs.
+    pub fn new(inputs: Vec<Vec<f32>>, targets: Vec<Vec<f32>>) -> Self {
+        assert_eq!(
+            inputs.len(),
+            targets.len(),
+            "every input needs exactly one target"
+        );
+        Self { inputs, targets }
+    }
+
+    pub fn push(&mut self, input: Vec<f32>, target: Vec<f32>) {
+        self.inputs.push(input);
+        self.targets.push(target);
+    }
+
+    pub fn into_parts(self) -> (Vec<Vec<f32>>, Vec<Vec<f32>>) {
+        (self.inputs, self.targets)
+    }
+}
+
+impl Dataset for VecDataset {
+    fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    fn get(&self, index: usize) -> (Vec<f32>, Vec<f32>) {
+        (self.inputs[index].clone(), self.targets[index].clone())
+    }
+
+    fn targets(&self) -> Vec<Vec<f32>> {
+        self.targets.clone()
+    }
+}
+
+/// Samples computed on demand from their index, so they never have to be
+/// held in memory all at once.
+pub struct Generated<F: Fn(usize) -> (Vec<f32>, Vec<f32>)> {
+    len: usize,
+    generate: F,
+}
+
+impl<F: Fn(usize) -> (Vec<f32>, Vec<f32>)> Generated<F> {
+    pub fn new(len: usize, generate: F) -> Self {
+        Self { len, generate }
+    }
+}
+
+impl<F: Fn(usize) -> (Vec<f32>, Vec<f32>)> Dataset for Generated<F> {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn get(&self, index: usize) -> (Vec<f32>, Vec<f32>) {
+        assert!(index < self.len, "index {} out of {}", index, self.len);
+        (self.generate)(index)
+    }
+}
+
+#[derive(Debug)]
+pub enum DataError {
+    Io(io::Error),
+    Csv(::csv::Error),
+    /// A value that isn't a number, at a 1-based line of the file and a
+    /// 0-based column.
+    Parse {
+        line: u64,
+        column: usize,
+        value: String,
+    },
+    /// A value of a categorical column that isn't one of the categories given
+    /// with `CsvDatasetBuilder::categories`.
+    UnknownCategory {
+        line: u64,
+        column: usize,
+        value: String,
+    },
+    /// A column name missing from the header, or an index past the last
+    /// column.
+    UnknownColumn(String),
+    /// The data doesn't have the expected layout.
+    Invalid(String),
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataError::Io(err) => write!(f, "io error: {}", err),
+            DataError::Csv(err) => write!(f, "csv error: {}", err),
+            DataError::Parse {
+                line,
+                column,
+                value,
+            } => write!(
+                f,
+                "line {}, column {}: '{}' is not a number",
+                line, column, value
+            ),
+            DataError::UnknownCategory {
+                line,
+                column,
+                value,
+            } => write!(
+                f,
+                "line {}, column {}: '{}' is not a known category",
+                line, column, value
+            ),
+            DataError::UnknownColumn(column) => write!(f, "unknown column '{}'", column),
+            DataError::Invalid(msg) => write!(f, "invalid data: {}", msg),
+        }
+    }
+}
+
+impl Error for DataError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DataError::Io(err) => Some(err),
+            DataError::Csv(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DataError {
+    fn from(err: io::Error) -> Self {
+        DataError::Io(err)
+    }
+}
+
+impl From<::csv::Error> for DataError {
+    fn from(err: ::csv::Error) -> Self {
+        DataError::Csv(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn in_memory_and_generated() {
+        let mut data = VecDataset::new(vec![vec![1.], vec![2.]], vec![vec![0.], vec![1.]]);
+        data.push(vec![3.], vec![0.]);
+        assert_eq!(data.len(), 3);
+        assert_eq!(data.get(2), (vec![3.], vec![0.]));
+        assert_eq!(data.targets(), vec![vec![0.], vec![1.], vec![0.]]);
+
+        let squares = Generated::new(4, |i| (vec![i as f32], vec![(i * i) as f32]));
+        assert_eq!(squares.len(), 4);
+        assert_eq!(squares.get(3), (vec![3.], vec![9.]));
+        let subset = Subset::new(&squares, vec![3, 1]);
+        assert_eq!(subset.targets(), vec![vec![9.], vec![1.]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "every input needs exactly one target")]
+    fn mismatched_pairs() {
+        (vec![vec![1.], vec![2.]], vec![vec![0.]]).len();
+    }
+}
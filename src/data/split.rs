@@ -0,0 +1,285 @@
+//! Splitting a dataset into train, validation and test parts, and into the
+//! folds of k-fold cross-validation. Stratified variants keep the share of
+//! every class the same in every part. A single whole number target, such as
+//! `mnist::Labels::Index`, is the class itself; other targets are classed by
+//! `metrics::class_of`.
+
+use crate::data::{Dataset, Subset};
+use crate::metrics::class_of;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::collections::BTreeMap;
+
+// The class a sample is stratified by.
+fn stratum(target: &[f32]) -> usize {
+    match target {
+        [index] if *index >= 0. && index.fract() == 0. => *index as usize,
+        _ => class_of(target),
+    }
+}
+
+// Indices of every class, in order of class and then of index.
+fn classes<D: Dataset + ?Sized>(dataset: &D) -> Vec<Vec<usize>> {
+    let mut classes: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (index, target) in dataset.targets().iter().enumerate() {
+        classes.entry(stratum(target)).or_default().push(index);
+    }
+    classes.into_values().collect()
+}
+
+// Consecutive parts of `indices` sized in proportion to `fractions`.
+fn partition(indices: &[usize], fractions: &[f32]) -> Vec<Vec<usize>> {
+    let total: f32 = fractions.iter().sum();
+    let mut start = 0;
+    let mut cumulative = 0.;
+    fractions
+        .iter()
+        .map(|fraction| {
+            cumulative += fraction;
+            let end = ((cumulative / total) * indices.len() as f32).round() as usize;
+            let end = end.clamp(start, indices.len());
+            let part = indices[start..end].to_vec();
+            start = end;
+            part
+        })
+        .collect()
+}
+
+/// Shuffles the samples and splits them into one part per fraction, in
+/// proportion to the fractions.
+pub fn random_split<'a, D: Dataset + ?Sized>(
+    dataset: &'a D,
+    fractions: &[f32],
+    seed: u64,
+) -> Vec<Subset<'a, D>> {
+    let mut indices: Vec<usize> = (0..dataset.len()).collect();
+    indices.shuffle(&mut ChaCha8Rng::seed_from_u64(seed));
+    partition(&indices, fractions)
+        .into_iter()
+        .map(|part| Subset::new(dataset, part))
+        .collect()
+}
+
+/// Like `random_split`, but splits every class separately so that each part
+/// keeps the class balance of the whole dataset.
+pub fn stratified_split<'a, D: Dataset + ?Sized>(
+    dataset: &'a D,
+    fractions: &[f32],
+    seed: u64,
+) -> Vec<Subset<'a, D>> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut parts: Vec<Vec<usize>> = vec![vec![]; fractions.len()];
+    for mut class in classes(dataset) {
+        class.shuffle(&mut rng);
+        for (part, indices) in parts.iter_mut().zip(partition(&class, fractions)) {
+            part.extend(indices);
+        }
+    }
+    parts
+        .into_iter()
+        .map(|mut part| {
+            part.shuffle(&mut rng);
+            Subset::new(dataset, part)
+        })
+        .collect()
+}
+
+/// The samples trained on and the samples held out in one round of
+/// cross-validation.
+pub struct Fold<'a, D: Dataset + ?Sized> {
+    pub train: Subset<'a, D>,
+    pub validation: Subset<'a, D>,
+}
+
+/// Iterates over `k` folds, each holding out a different `k`th of the
+/// samples. Without a seed every fold holds out consecutive samples.
+pub struct KFold<'a, D: Dataset + ?Sized> {
+    dataset: &'a D,
+    k: usize,
+    stratified: bool,
+    seed: Option<u64>,
+    held_out: Option<Vec<Vec<usize>>>,
+    next: usize,
+}
+
+impl<'a, D: Dataset + ?Sized> KFold<'a, D> {
+    /// Panics unless there are at least 2 folds and no more folds than samples.
+    pub fn new(dataset: &'a D, k: usize) -> Self {
+        assert!(
+            k >= 2 && k <= dataset.len(),
+            "can't split {} samples into {} folds",
+            dataset.len(),
+            k
+        );
+        Self {
+            dataset,
+            k,
+            stratified: false,
+            seed: None,
+            held_out: None,
+            next: 0,
+        }
+    }
+
+    /// Deals the samples of every class out over the folds in turn, so that
+    /// each fold keeps the class balance of the whole dataset.
+    pub fn stratified(mut self) -> Self {
+        self.stratified = true;
+        self
+    }
+
+    /// Shuffles the samples before assigning them to folds.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    fn assign(&self) -> Vec<Vec<usize>> {
+        let mut rng = self.seed.map(ChaCha8Rng::seed_from_u64);
+        let mut held_out = vec![vec![]; self.k];
+        if self.stratified {
+            let mut position = 0;
+            for mut class in classes(self.dataset) {
+                if let Some(rng) = rng.as_mut() {
+                    class.shuffle(rng);
+                }
+                for index in class {
+                    held_out[position % self.k].push(index);
+                    position += 1;
+                }
+            }
+            for fold in held_out.iter_mut() {
+                fold.sort_unstable();
+            }
+        } else {
+            let mut indices: Vec<usize> = (0..self.dataset.len()).collect();
+            if let Some(rng) = rng.as_mut() {
+                indices.shuffle(rng);
+            }
+            let (size, rest) = (indices.len() / self.k, indices.len() % self.k);
+            let mut start = 0;
+            for (i, fold) in held_out.iter_mut().enumerate() {
+                let end = start + size + usize::from(i < rest);
+                *fold = indices[start..end].to_vec();
+                start = end;
+            }
+        }
+        held_out
+    }
+}
+
+impl<'a, D: Dataset + ?Sized> Iterator for KFold<'a, D> {
+    type Item = Fold<'a, D>;
+
+    fn next(&mut self) -> Option<Fold<'a, D>> {
+        if self.held_out.is_none() {
+            self.held_out = Some(self.assign());
+        }
+        let held_out = self.held_out.as_ref()?.get(self.next)?.clone();
+        self.next += 1;
+        let mut in_fold = vec![false; self.dataset.len()];
+        for index in &held_out {
+            in_fold[*index] = true;
+        }
+        let train = (0..self.dataset.len())
+            .filter(|index| !in_fold[*index])
+            .collect();
+        Some(Fold {
+            train: Subset::new(self.dataset, train),
+            validation: Subset::new(self.dataset, held_out),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::Generated;
+
+    // 8 samples of class 0 followed by 4 of class 1
+    fn imbalanced() -> Generated<impl Fn(usize) -> (Vec<f32>, Vec<f32>)> {
+        Generated::new(12, |i| {
+            let class = if i < 8 { 0. } else { 1. };
+            (vec![i as f32], vec![class])
+        })
+    }
+
+    fn positives<D: Dataset + ?Sized>(subset: &Subset<'_, D>) -> usize {
+        subset.targets().iter().filter(|t| t[0] == 1.).count()
+    }
+
+    #[test]
+    fn splits() {
+        let data = imbalanced();
+        let parts = random_split(&data, &[0.5, 0.25, 0.25], 1);
+        let sizes: Vec<usize> = parts.iter().map(|part| part.len()).collect();
+        assert_eq!(sizes, vec![6, 3, 3]);
+        let mut all: Vec<usize> = parts.iter().flat_map(|p| p.indices().to_vec()).collect();
+        all.sort_unstable();
+        assert_eq!(all, (0..12).collect::<Vec<usize>>());
+        assert_eq!(
+            random_split(&data, &[0.5, 0.5], 1)[0].indices(),
+            random_split(&data, &[0.5, 0.5], 1)[0].indices()
+        );
+
+        let parts = stratified_split(&data, &[0.75, 0.25], 1);
+        assert_eq!(parts[0].len(), 9);
+        assert_eq!(positives(&parts[0]), 3);
+        assert_eq!(positives(&parts[1]), 1);
+    }
+
+    #[test]
+    fn k_fold() {
+        let data = imbalanced();
+        let folds: Vec<Vec<usize>> = KFold::new(&data, 5)
+            .map(|fold| {
+                assert_eq!(fold.train.len() + fold.validation.len(), 12);
+                fold.validation.indices().to_vec()
+            })
+            .collect();
+        assert_eq!(folds[0], vec![0, 1, 2]);
+        assert_eq!(folds[4], vec![10, 11]);
+
+        let mut held_out: Vec<usize> = KFold::new(&data, 3)
+            .with_seed(2)
+            .flat_map(|fold| fold.validation.indices().to_vec())
+            .collect();
+        held_out.sort_unstable();
+        assert_eq!(held_out, (0..12).collect::<Vec<usize>>());
+
+        for fold in KFold::new(&data, 4).stratified().with_seed(3) {
+            assert_eq!(fold.validation.len(), 3);
+            assert_eq!(positives(&fold.validation), 1);
+            assert_eq!(positives(&fold.train), 3);
+        }
+    }
+
+    #[test]
+    fn stratifies_class_indices() {
+        // 12, 8 and 4 samples of classes 0, 1 and 2
+        let data = Generated::new(24, |i| {
+            let class = if i < 12 {
+                0.
+            } else if i < 20 {
+                1.
+            } else {
+                2.
+            };
+            (vec![i as f32], vec![class])
+        });
+        let counts = |subset: &Subset<'_, _>| -> Vec<usize> {
+            let targets = subset.targets();
+            (0..3)
+                .map(|class| targets.iter().filter(|t| t[0] == class as f32).count())
+                .collect()
+        };
+        for fold in KFold::new(&data, 4).stratified().with_seed(5) {
+            assert_eq!(counts(&fold.validation), vec![3, 2, 1]);
+            assert_eq!(counts(&fold.train), vec![9, 6, 3]);
+        }
+        let parts = stratified_split(&data, &[0.5, 0.5], 2);
+        assert_eq!(counts(&parts[0]), vec![6, 4, 2]);
+        assert_eq!(counts(&parts[1]), vec![6, 4, 2]);
+    }
+}
@@ -0,0 +1,370 @@
+//! Checks the gradients computed by `Layer::backwards` against central finite
+//! differences of the loss, to catch mistakes in hand-written backward passes.
+
+use crate::layers::Layer;
+use crate::losses::Loss;
+use crate::node::RannV2;
+use crate::rann::Rann;
+use crate::sequential::Sequential;
+use std::fmt;
+
+/// How far every value is moved either way for the finite differences. The
+/// forward pass runs in f32, so much smaller steps drown the differences in
+/// rounding noise.
+pub const STEP: f32 = 1e-2;
+
+/// Gradients smaller than this are compared by their absolute error, scaled
+/// as if they were this large. Passing `GradCheck::assert_below(tolerance)`
+/// therefore means every gradient is within `tolerance` of the finite
+/// difference relatively, or within `tolerance * GRADIENT_FLOOR` absolutely.
+pub const GRADIENT_FLOOR: f32 = 1e-4;
+
+/// Anything whose gradients can be checked: a single layer or a whole model.
+pub trait AsLayer {
+    fn as_layer(&mut self) -> &mut dyn Layer;
+}
+
+impl<L: Layer> AsLayer for L {
+    fn as_layer(&mut self) -> &mut dyn Layer {
+        self
+    }
+}
+
+impl AsLayer for dyn Layer {
+    fn as_layer(&mut self) -> &mut dyn Layer {
+        self
+    }
+}
+
+impl AsLayer for Rann {
+    fn as_layer(&mut self) -> &mut dyn Layer {
+        self.network_mut() as &mut Sequential
+    }
+}
+
+impl AsLayer for RannV2 {
+    fn as_layer(&mut self) -> &mut dyn Layer {
+        self.network_mut() as &mut Sequential
+    }
+}
+
+/// The worst disagreement found for the parameters of one layer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayerCheck {
+    /// The position of the layer in its model and the kind it saves as.
+    pub name: String,
+    /// The number of values checked.
+    pub parameters: usize,
+    pub max_relative_error: f32,
+}
+
+/// What `gradcheck` found, per layer and for the input.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GradCheck {
+    pub layers: Vec<LayerCheck>,
+    pub input: f32,
+}
+
+impl GradCheck {
+    pub fn max_relative_error(&self) -> f32 {
+        self.layers
+            .iter()
+            .map(|layer| layer.max_relative_error)
+            .fold(self.input, f32::max)
+    }
+
+    /// Panics with the full report if any error reaches `tolerance`, see
+    /// `GRADIENT_FLOOR` for gradients close to zero.
+    pub fn assert_below(&self, tolerance: f32) {
+        assert!(
+            self.max_relative_error() < tolerance,
+            "gradients disagree beyond {}:\n{}",
+            tolerance,
+            self
+        );
+    }
+}
+
+impl fmt::Display for GradCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for layer in self.layers.iter() {
+            writeln!(
+                f,
+                "{}: {} parameters, max relative error {:.2e}",
+                layer.name, layer.parameters, layer.max_relative_error
+            )?;
+        }
+        write!(f, "input: max relative error {:.2e}", self.input)
+    }
+}
+
+// Relative to the larger gradient, but never to less than GRADIENT_FLOOR so
+// that the rounding noise of f32 doesn't swamp gradients close to zero.
+fn relative_error(analytic: f32, numeric: f32) -> f32 {
+    (analytic - numeric).abs() / analytic.abs().max(numeric.abs()).max(GRADIENT_FLOOR)
+}
+
+fn cost(layer: &mut dyn Layer, input: &[f32], target: &[f32], loss: &dyn Loss) -> f32 {
+    loss.compute(&layer.forward(input), target)
+}
+
+// Central difference of the cost as `set` moves one value by ±STEP.
+fn numeric<F: FnMut(&mut dyn Layer, f32)>(
+    layer: &mut dyn Layer,
+    input: &[f32],
+    target: &[f32],
+    loss: &dyn Loss,
+    mut set: F,
+) -> f32 {
+    set(layer, STEP);
+    let plus = cost(layer, input, target, loss) as f64;
+    set(layer, -2. * STEP);
+    let minus = cost(layer, input, target, loss) as f64;
+    set(layer, STEP);
+    ((plus - minus) / (2. * STEP as f64)) as f32
+}
+
+/// Compares the gradients `backwards` gives for `input` and `target` under
+/// `loss` with central finite differences, for every parameter and every
+/// input value. Layers of a container such as `Sequential` or a `Rann` are
+/// reported one by one. Gradients already accumulated are cleared.
+///
+/// Finite differences are meaningless where an activation has a kink, so
+/// inputs that put a ReLU right at zero can report large errors.
+pub fn gradcheck<M: AsLayer + ?Sized>(
+    model: &mut M,
+    input: &[f32],
+    target: &[f32],
+    loss: &dyn Loss,
+) -> GradCheck {
+    let layer = model.as_layer();
+    layer.zero_gradients();
+    let output = layer.forward(input);
+    let input_gradients = layer.backwards(&loss.compute_derivative(&output, target));
+    let analytic: Vec<Vec<f32>> = layer
+        .parameters()
+        .iter()
+        .map(|parameter| parameter.gradients.to_vec())
+        .collect();
+    layer.zero_gradients();
+
+    // how many of the parameter tensors belong to each reported layer
+    let mut groups: Vec<(String, usize)> = layer
+        .sublayers()
+        .into_iter()
+        .enumerate()
+        .map(|(i, sublayer)| (name(i, sublayer), sublayer.parameters().len()))
+        .collect();
+    if groups.is_empty() {
+        groups.push((name(0, layer), analytic.len()));
+    }
+
+    let mut tensor = 0;
+    let mut layers = vec![];
+    for (name, tensors) in groups {
+        let mut check = LayerCheck {
+            name,
+            parameters: 0,
+            max_relative_error: 0.,
+        };
+        for (t, gradients) in analytic.iter().enumerate().skip(tensor).take(tensors) {
+            for (i, gradient) in gradients.iter().enumerate() {
+                let numeric = numeric(layer, input, target, loss, |layer, by| {
+                    layer.parameters()[t].values[i] += by;
+                });
+                check.parameters += 1;
+                check.max_relative_error = check
+                    .max_relative_error
+                    .max(relative_error(*gradient, numeric));
+            }
+        }
+        tensor += tensors;
+        layers.push(check);
+    }
+
+    let mut moved = input.to_vec();
+    let mut input_error: f32 = 0.;
+    for (i, gradient) in input_gradients.iter().enumerate() {
+        moved[i] += STEP;
+        let plus = cost(layer, &moved, target, loss) as f64;
+        moved[i] -= 2. * STEP;
+        let minus = cost(layer, &moved, target, loss) as f64;
+        moved[i] = input[i];
+        let numeric = ((plus - minus) / (2. * STEP as f64)) as f32;
+        input_error = input_error.max(relative_error(*gradient, numeric));
+    }
+
+    GradCheck {
+        layers,
+        input: input_error,
+    }
+}
+
+fn name(index: usize, layer: &mut dyn Layer) -> String {
+    match layer.to_record() {
+        Ok(record) => format!("{} {}", index, record.kind),
+        Err(_) => index.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::activations::{Activation, Linear, Sigmoid, Tanh};
+    use crate::layers::dense::LayerDense;
+    use crate::layers::Parameter;
+    use crate::losses::{CategoricalCrossEntropy, MeanSquaredError};
+    use crate::node::LayerSoftmax;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    const TOLERANCE: f32 = 1e-2;
+
+    #[test]
+    fn dense_layers() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for activation in 0..3 {
+            let activation: Box<dyn crate::activations::ActivationFunc> = match activation {
+                0 => Box::new(Linear::new()),
+                1 => Box::new(Sigmoid::new()),
+                _ => Box::new(Tanh::new()),
+            };
+            let mut layer = LayerDense::new(3, 2, activation);
+            layer.initialize(&mut rng);
+            let check = gradcheck(
+                &mut layer,
+                &[0.5, -0.3, 0.8],
+                &[0.2, -0.1],
+                &MeanSquaredError::new(),
+            );
+            assert_eq!(check.layers.len(), 1);
+            assert_eq!(check.layers[0].parameters, 8);
+            check.assert_below(TOLERANCE);
+        }
+    }
+
+    #[test]
+    fn saturated_sigmoid() {
+        // an output of about 0.998, where the derivative is close to zero
+        let mut layer = LayerDense::new(2, 1, Box::new(Sigmoid::new()));
+        layer.weights.fill(3.);
+        let check = gradcheck(&mut layer, &[1., 1.], &[0.], &MeanSquaredError::new());
+        check.assert_below(TOLERANCE);
+    }
+
+    #[test]
+    fn models() {
+        let mut rann = Rann::with_activations(&[
+            (3, Activation::Linear),
+            (4, Activation::Tanh),
+            (3, Activation::Sigmoid),
+            (2, Activation::Softmax),
+        ])
+        .with_seed(3);
+        let check = gradcheck(
+            &mut rann,
+            &[0.3, -0.2, 0.9],
+            &[0., 1.],
+            &CategoricalCrossEntropy::new(),
+        );
+        let names: Vec<&str> = check.layers.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, vec!["0 dense", "1 dense", "2 dense", "3 softmax"]);
+        assert_eq!(check.layers[0].parameters, 16);
+        assert_eq!(check.layers[3].parameters, 0);
+        check.assert_below(TOLERANCE);
+
+        let mut rannv2 = RannV2::new(vec![3, 4, 2]).with_seed(1);
+        let check = gradcheck(
+            &mut rannv2,
+            &[0.3, -0.2, 0.9],
+            &[1., 0.],
+            &CategoricalCrossEntropy::new(),
+        );
+        assert_eq!(check.layers[0].name, "0 node_dense");
+        check.assert_below(TOLERANCE);
+
+        let mut softmax: Box<dyn Layer> = Box::new(LayerSoftmax::new(3));
+        let check = gradcheck(
+            softmax.as_mut(),
+            &[0.1, 0.5, -0.4],
+            &[0., 0., 1.],
+            &MeanSquaredError::new(),
+        );
+        check.assert_below(TOLERANCE);
+    }
+
+    #[test]
+    fn catches_wrong_gradients() {
+        // y = w * x with a backward pass that forgets x
+        struct Scale {
+            weight: [f32; 1],
+            gradient: [f32; 1],
+        }
+        impl Layer for Scale {
+            fn forward(&mut self, inputs: &[f32]) -> Vec<f32> {
+                vec![self.weight[0] * inputs[0]]
+            }
+            fn backwards(&mut self, derivatives: &[f32]) -> Vec<f32> {
+                self.gradient[0] += derivatives[0];
+                vec![derivatives[0] * self.weight[0]]
+            }
+            fn outputs(&self) -> usize {
+                1
+            }
+            fn parameters(&mut self) -> Vec<Parameter<'_>> {
+                vec![Parameter {
+                    values: &mut self.weight,
+                    gradients: &mut self.gradient,
+                }]
+            }
+        }
+
+        let mut layer = Scale {
+            weight: [0.5],
+            gradient: [0.],
+        };
+        let check = gradcheck(&mut layer, &[3.], &[0.], &MeanSquaredError::new());
+        assert_eq!(check.layers[0].name, "0");
+        assert!(check.layers[0].max_relative_error > 0.5, "{}", check);
+        assert!(check.input < TOLERANCE, "{}", check);
+        assert_eq!(layer.gradient, [0.]);
+    }
+
+    #[test]
+    fn catches_small_errors() {
+        // y = w * x with a weight gradient off by 5e-5
+        struct Scale {
+            weight: [f32; 1],
+            gradient: [f32; 1],
+            input: f32,
+        }
+        impl Layer for Scale {
+            fn forward(&mut self, inputs: &[f32]) -> Vec<f32> {
+                self.input = inputs[0];
+                vec![self.weight[0] * inputs[0]]
+            }
+            fn backwards(&mut self, derivatives: &[f32]) -> Vec<f32> {
+                self.gradient[0] += derivatives[0] * self.input + 5e-5;
+                vec![derivatives[0] * self.weight[0]]
+            }
+            fn outputs(&self) -> usize {
+                1
+            }
+            fn parameters(&mut self) -> Vec<Parameter<'_>> {
+                vec![Parameter {
+                    values: &mut self.weight,
+                    gradients: &mut self.gradient,
+                }]
+            }
+        }
+
+        let mut layer = Scale {
+            weight: [0.5],
+            gradient: [0.],
+            input: 0.,
+        };
+        let check = gradcheck(&mut layer, &[1.], &[0.4999], &MeanSquaredError::new());
+        assert!(check.layers[0].max_relative_error > 0.1, "{}", check);
+        assert!(check.input < TOLERANCE, "{}", check);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::tensor::Tensor;
+use rand::Rng;
+use std::f32::consts::PI;
+
+/// How the weights or biases of a layer are filled when it is created.
+/// `fan_in` and `fan_out` are the number of inputs and outputs of the layer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Initializer {
+    Zeros,
+    Constant(f32),
+    Uniform { low: f32, high: f32 },
+    Normal { mean: f32, std_dev: f32 },
+    XavierUniform,
+    XavierNormal,
+    HeUniform,
+    HeNormal,
+    LeCunUniform,
+    LeCunNormal,
+    Orthogonal { gain: f32 },
+}
+
+fn uniform<R: Rng + ?Sized>(rng: &mut R, low: f32, high: f32) -> f32 {
+    low + (high - low) * rng.gen::<f32>()
+}
+
+// Box-Muller transform, so no extra distribution crate is needed.
+fn normal<R: Rng + ?Sized>(rng: &mut R, mean: f32, std_dev: f32) -> f32 {
+    let u1 = 1. - rng.gen::<f32>();
+    let u2 = rng.gen::<f32>();
+    mean + std_dev * (-2. * u1.ln()).sqrt() * (2. * PI * u2).cos()
+}
+
+impl Initializer {
+    /// Builds a tensor of `shape` for a layer with the given fans.
+    pub fn initialize<R: Rng + ?Sized>(
+        &self,
+        shape: &[usize],
+        fan_in: usize,
+        fan_out: usize,
+        rng: &mut R,
+    ) -> Tensor {
+        let fan_in = fan_in.max(1) as f32;
+        let fan_out = fan_out.max(1) as f32;
+        match *self {
+            Initializer::Zeros => Tensor::zeros(shape),
+            Initializer::Constant(value) => Tensor::filled(shape, value),
+            Initializer::Uniform { low, high } => {
+                Tensor::from_fn(shape, |_| uniform(rng, low, high))
+            }
+            Initializer::Normal { mean, std_dev } => {
+                Tensor::from_fn(shape, |_| normal(rng, mean, std_dev))
+            }
+            Initializer::XavierUniform => {
+                let limit = (6. / (fan_in + fan_out)).sqrt();
+                Tensor::from_fn(shape, |_| uniform(rng, -limit, limit))
+            }
+            Initializer::XavierNormal => {
+                let std_dev = (2. / (fan_in + fan_out)).sqrt();
+                Tensor::from_fn(shape, |_| normal(rng, 0., std_dev))
+            }
+            Initializer::HeUniform => {
+                let limit = (6. / fan_in).sqrt();
+                Tensor::from_fn(shape, |_| uniform(rng, -limit, limit))
+            }
+            Initializer::HeNormal => {
+                let std_dev = (2. / fan_in).sqrt();
+                Tensor::from_fn(shape, |_| normal(rng, 0., std_dev))
+            }
+            Initializer::LeCunUniform => {
+                let limit = (3. / fan_in).sqrt();
+                Tensor::from_fn(shape, |_| uniform(rng, -limit, limit))
+            }
+            Initializer::LeCunNormal => {
+                let std_dev = (1. / fan_in).sqrt();
+                Tensor::from_fn(shape, |_| normal(rng, 0., std_dev))
+            }
+            Initializer::Orthogonal { gain } => orthogonal(shape, gain, rng),
+        }
+    }
+}
+
+// Gram-Schmidt on a random normal matrix. The shorter side ends up with
+// orthonormal vectors; anything beyond rank 2 is treated as
+// [shape[0], rest].
+fn orthogonal<R: Rng + ?Sized>(shape: &[usize], gain: f32, rng: &mut R) -> Tensor {
+    let rows = shape.first().cloned().unwrap_or(1);
+    let columns: usize = shape.iter().skip(1).product();
+    let transposed = rows > columns;
+    let (count, size) = if transposed {
+        (columns, rows)
+    } else {
+        (rows, columns)
+    };
+
+    let mut vectors: Vec<Vec<f32>> = Vec::with_capacity(count);
+    while vectors.len() < count {
+        let mut v: Vec<f32> = (0..size).map(|_| normal(rng, 0., 1.)).collect();
+        for u in vectors.iter() {
+            let projection: f32 = v.iter().zip(u).map(|(a, b)| a * b).sum();
+            for (a, b) in v.iter_mut().zip(u) {
+                *a -= projection * b;
+            }
+        }
+        let norm = v.iter().map(|a| a * a).sum::<f32>().sqrt();
+        // a degenerate draw is simply retried
+        if norm > 1e-6 {
+            vectors.push(v.iter().map(|a| gain * a / norm).collect());
+        }
+    }
+
+    let matrix = Tensor::from_rows(&vectors);
+    let matrix = if transposed {
+        matrix.transpose()
+    } else {
+        matrix
+    };
+    matrix.reshape(shape)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn constants() {
+        let mut rng = rand::thread_rng();
+        let t = Initializer::Zeros.initialize(&[2, 3], 3, 2, &mut rng);
+        assert_eq!(t, Tensor::zeros(&[2, 3]));
+        let t = Initializer::Constant(0.2).initialize(&[4], 3, 4, &mut rng);
+        assert_eq!(t.data(), &[0.2; 4]);
+    }
+
+    #[test]
+    fn uniform_ranges() {
+        let mut rng = rand::thread_rng();
+        let t = Initializer::Uniform {
+            low: -2.,
+            high: -1.,
+        }
+        .initialize(&[100, 10], 10, 100, &mut rng);
+        assert!(t.min() >= -2. && t.max() < -1.);
+
+        let limit = (6_f32 / 110.).sqrt();
+        let t = Initializer::XavierUniform.initialize(&[100, 10], 10, 100, &mut rng);
+        assert!(t.min() >= -limit && t.max() <= limit);
+        assert!(t.min() < 0. && t.max() > 0.);
+    }
+
+    #[test]
+    fn normal_statistics() {
+        let mut rng = rand::thread_rng();
+        let t = Initializer::Normal {
+            mean: 1.,
+            std_dev: 2.,
+        }
+        .initialize(&[100, 100], 100, 100, &mut rng);
+        let mean = t.mean();
+        let std_dev = t.map(|x| (x - mean).powi(2)).mean().sqrt();
+        assert!((mean - 1.).abs() < 0.1);
+        assert!((std_dev - 2.).abs() < 0.1);
+
+        let t = Initializer::HeNormal.initialize(&[200, 50], 50, 200, &mut rng);
+        let std_dev = t.map(|x| x * x).mean().sqrt();
+        assert!((std_dev - 0.2).abs() < 0.02);
+    }
+
+    #[test]
+    fn orthogonal_rows_and_columns() {
+        let mut rng = rand::thread_rng();
+        for shape in [[3, 5], [5, 3]].iter() {
+            let t = Initializer::Orthogonal { gain: 1. }
+                .initialize(shape, shape[1], shape[0], &mut rng);
+            assert_eq!(t.shape(), shape);
+            let gram = if shape[0] <= shape[1] {
+                t.matmul(&t.transpose())
+            } else {
+                t.transpose().matmul(&t)
+            };
+            let n = gram.shape()[0];
+            for i in 0..n {
+                for j in 0..n {
+                    let expected = if i == j { 1. } else { 0. };
+                    assert!((gram.get(&[i, j]) - expected).abs() < 1e-4);
+                }
+            }
+        }
+    }
+}
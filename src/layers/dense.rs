@@ -1,113 +1,186 @@
-use crate::activations::{ActivationFunc, Sigmoid, RELU};
-use crate::math::{cut_val, dot};
-use rand::Rng;
+use crate::activations::{ActivationFunc, IntoActivation};
+use crate::initializers::Initializer;
+use crate::layers::{Layer, Parameter};
+use crate::serialization::{LayerRecord, ModelError, Registry};
+use crate::tensor::Tensor;
+use rand::RngCore;
 
 pub struct LayerDense {
-    pub weights: Vec<Vec<f32>>,
-    biases: Vec<f32>,
+    pub weights: Tensor,
+    weight_gradients: Tensor,
+    weight_initializer: Initializer,
+    biases: Tensor,
+    bias_gradients: Tensor,
+    bias_initializer: Initializer,
+    use_bias: bool,
     output: Option<Vec<f32>>,
     input: Option<Vec<f32>>,
-    input_nodes_count: usize,
-    output_nodes_count: usize,
     activation: Box<dyn ActivationFunc>,
 }
 
 impl LayerDense {
-    pub fn new(
-        input_nodes: usize,
-        output_nodes: usize,
-        activation: Box<dyn ActivationFunc>,
-    ) -> Self {
-        Self {
-            weights: vec![0; output_nodes]
-                .iter()
-                .map(|_| Self::gen_weights(input_nodes))
-                .collect(),
-            biases: vec![0.2; output_nodes],
+    pub fn new<A: IntoActivation>(input_nodes: usize, output_nodes: usize, activation: A) -> Self {
+        let mut layer = Self {
+            weights: Tensor::zeros(&[output_nodes, input_nodes]),
+            weight_gradients: Tensor::zeros(&[output_nodes, input_nodes]),
+            weight_initializer: Initializer::Uniform { low: 0., high: 1. },
+            biases: Tensor::zeros(&[output_nodes]),
+            bias_gradients: Tensor::zeros(&[output_nodes]),
+            bias_initializer: Initializer::Constant(0.2),
+            use_bias: true,
             output: None,
             input: None,
-            input_nodes_count: input_nodes,
-            output_nodes_count: output_nodes,
-            activation,
+            activation: activation.into_activation(),
+        };
+        layer.initialize(&mut rand::thread_rng());
+        layer
+    }
+
+    /// Drops the bias term, so the layer computes `activation(weights * x)`.
+    pub fn without_bias(mut self) -> Self {
+        self.biases.fill(0.);
+        self.bias_initializer = Initializer::Zeros;
+        self.use_bias = false;
+        self
+    }
+
+    pub fn with_initializer(mut self, initializer: Initializer) -> Self {
+        self.weight_initializer = initializer;
+        self.initialize(&mut rand::thread_rng());
+        self
+    }
+
+    pub fn with_bias_initializer(mut self, initializer: Initializer) -> Self {
+        if self.use_bias {
+            self.bias_initializer = initializer;
+            self.initialize(&mut rand::thread_rng());
         }
+        self
     }
 
-    fn gen_weights(count: usize) -> Vec<f32> {
-        let mut rng = rand::thread_rng();
-        vec![0; count].iter().map(|_| rng.gen::<f32>()).collect()
+    /// Rebuilds a layer saved with `to_record`. A record without biases gives
+    /// a layer without bias.
+    pub fn from_record(record: &LayerRecord, registry: &Registry) -> Result<Self, ModelError> {
+        let shape = [record.outputs, record.inputs];
+        let weights = record.expect_tensor("weights", &shape)?.clone();
+        let use_bias = record.tensor("biases").is_some();
+        let biases = if use_bias {
+            record.expect_tensor("biases", &[record.outputs])?.clone()
+        } else {
+            Tensor::zeros(&[record.outputs])
+        };
+        Ok(Self {
+            weights,
+            weight_gradients: Tensor::zeros(&shape),
+            weight_initializer: Initializer::Uniform { low: 0., high: 1. },
+            biases,
+            bias_gradients: Tensor::zeros(&[record.outputs]),
+            bias_initializer: if use_bias {
+                Initializer::Constant(0.2)
+            } else {
+                Initializer::Zeros
+            },
+            use_bias,
+            output: None,
+            input: None,
+            activation: registry.record_activation(record)?,
+        })
     }
+}
 
-    pub fn forward(&mut self, inputs: &Vec<f32>) -> Vec<f32> {
+impl Layer for LayerDense {
+    fn forward(&mut self, inputs: &[f32]) -> Vec<f32> {
         let output: Vec<f32> = self
             .weights
+            .matmul(&Tensor::vector(inputs))
+            .data()
             .iter()
-            .map(|cell_weight| dot(cell_weight, inputs))
-            .zip(&self.biases)
+            .zip(self.biases.data())
             .map(|(x, y)| self.activation.compute(x + y))
             .collect();
-        let without_act: Vec<f32> = self
-            .weights
+        self.output = Some(output.clone());
+        self.input = Some(inputs.to_vec());
+        output
+    }
+
+    fn backwards(&mut self, derivatives: &[f32]) -> Vec<f32> {
+        let outputs = self.output.as_ref().unwrap();
+        let deltas: Vec<f32> = outputs
             .iter()
-            .map(|w| dot(w, inputs))
-            .zip(&self.biases)
-            .map(|(x, y)| x + y)
+            .zip(derivatives)
+            .map(|(output, derivative)| self.activation.compute_derivative(*output) * derivative)
             .collect();
-        let output2 = output.clone();
-        self.output = Some(output);
-        self.input = Some(inputs.clone());
-        if self.output_nodes_count == 10 && false {
-            println!("Biases: {:?}", self.biases);
-            println!("Last output: {:?}", without_act);
+
+        let backward_derivatives = Tensor::vector(&deltas).matmul(&self.weights);
+
+        let inputs = self.input.as_ref().unwrap();
+        self.weight_gradients
+            .add_assign(&Tensor::outer(&deltas, inputs));
+        if self.use_bias {
+            self.bias_gradients.add_assign(&Tensor::vector(&deltas));
         }
-        output2
+        backward_derivatives.into_data()
     }
 
-    pub fn backward(&mut self, derivatives: &Vec<f32>) -> Vec<f32> {
-        let learning_rate = 0.1;
-        let outputs = self.output.as_ref().unwrap();
-        let mut backward_derivatives = vec![0.; self.input_nodes_count];
-
-        for i in 0..self.input_nodes_count {
-            let mut der = 0.;
-            for j in 0..self.output_nodes_count {
-                let weight = self.weights[j][i];
-                let out_deri = derivatives[j];
-                let output = cut_val(outputs[j], 0.98);
-                der += weight * self.activation.compute_derivative(output) * out_deri;
-            }
-            backward_derivatives[i] = der;
+    fn outputs(&self) -> usize {
+        self.biases.len()
+    }
+
+    fn initialize(&mut self, rng: &mut dyn RngCore) {
+        let (output_nodes, input_nodes) = (self.weights.shape()[0], self.weights.shape()[1]);
+        self.weights = self.weight_initializer.initialize(
+            &[output_nodes, input_nodes],
+            input_nodes,
+            output_nodes,
+            rng,
+        );
+        if self.use_bias {
+            self.biases =
+                self.bias_initializer
+                    .initialize(&[output_nodes], input_nodes, output_nodes, rng);
         }
+    }
 
-        if self.output_nodes_count == 10 && false {
-            println!("Backwards grads: {:?}", backward_derivatives);
+    fn parameters(&mut self) -> Vec<Parameter<'_>> {
+        let mut parameters = vec![Parameter {
+            values: self.weights.data_mut(),
+            gradients: self.weight_gradients.data_mut(),
+        }];
+        if self.use_bias {
+            parameters.push(Parameter {
+                values: self.biases.data_mut(),
+                gradients: self.bias_gradients.data_mut(),
+            });
         }
+        parameters
+    }
 
-        let inputs = self.input.clone().unwrap();
-        for i in 0..self.output_nodes_count {
-            let output = cut_val(outputs[i], 0.98);
-            for j in 0..self.weights[i].len() {
-                //  let current_w = self.weights[i][j];
-                let mut update = learning_rate
-                    * self.activation.compute_derivative(output)
-                    * inputs[j]
-                    * derivatives[i];
-                update = cut_val(update, 2.);
-                self.weights[i][j] -= update;
-                // println!("Weight update: {}", update);
-            }
+    fn to_record(&self) -> Result<LayerRecord, ModelError> {
+        let mut record =
+            LayerRecord::new("dense", self.weights.shape()[1], self.weights.shape()[0]);
+        record.activation = Some(self.activation.name().to_string());
+        record
+            .tensors
+            .push(("weights".to_string(), self.weights.clone()));
+        if self.use_bias {
+            record
+                .tensors
+                .push(("biases".to_string(), self.biases.clone()));
         }
-        backward_derivatives
+        Ok(record)
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::activations::{Linear, Sigmoid};
+    use crate::optimizers::{Optimizer, Sgd};
 
     #[test]
     fn dense_single_layer() {
         let mut layer = LayerDense::new(3, 7, Box::new(Sigmoid::new()));
-        let output = layer.forward(&vec![1.0, 1.0, 1.0]);
+        let output = layer.forward(&[1.0, 1.0, 1.0]);
         assert_eq!(output.len(), 7);
     }
 
@@ -116,7 +189,7 @@ mod test {
         let mut layer1 = LayerDense::new(5, 4, Box::new(Sigmoid::new()));
         let mut layer2 = LayerDense::new(4, 3, Box::new(Sigmoid::new()));
         let mut layer3 = LayerDense::new(3, 7, Box::new(Sigmoid::new()));
-        let input = &vec![1.0, -3.0, 3.0, 1.0, 6.0];
+        let input = &[1.0, -3.0, 3.0, 1.0, 6.0];
         let output = layer1.forward(input);
         assert_eq!(output.len(), 4);
         let output = layer2.forward(&output);
@@ -124,4 +197,58 @@ mod test {
         let output = layer3.forward(&output);
         assert_eq!(output.len(), 7);
     }
+
+    #[test]
+    fn dense_bias_is_trained() {
+        let mut layer = LayerDense::new(2, 1, Box::new(Linear::new()));
+        layer.forward(&[0., 0.]);
+        layer.backwards(&[1.]);
+        let parameters = layer.parameters();
+        assert_eq!(parameters.len(), 2);
+        assert_eq!(parameters[1].gradients, &[1.]);
+
+        // with zero inputs only the bias can move the output
+        let mut optimizer = Sgd::new(0.1);
+        for _ in 0..100 {
+            let output = layer.forward(&[0., 0.]);
+            layer.backwards(&[output[0] - 2.]);
+            optimizer.step(&mut layer.parameters());
+            layer.zero_gradients();
+        }
+        assert!((layer.forward(&[0., 0.])[0] - 2.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn dense_without_bias() {
+        let mut layer = LayerDense::new(2, 3, Box::new(Linear::new())).without_bias();
+        assert_eq!(layer.forward(&[0., 0.]), vec![0., 0., 0.]);
+        layer.backwards(&[1., 1., 1.]);
+        assert_eq!(layer.parameters().len(), 1);
+    }
+
+    #[test]
+    fn dense_initializers() {
+        let mut layer = LayerDense::new(3, 2, Box::new(Linear::new()))
+            .with_initializer(Initializer::Constant(0.5))
+            .with_bias_initializer(Initializer::Zeros);
+        assert_eq!(layer.forward(&[1., 2., 3.]), vec![3., 3.]);
+
+        let layer = LayerDense::new(30, 20, Box::new(Linear::new()))
+            .with_initializer(Initializer::HeNormal);
+        assert_eq!(layer.weights.shape(), &[20, 30]);
+        assert!(layer.weights.min() < 0.);
+    }
+
+    #[test]
+    fn dense_record_round_trip() {
+        let registry = Registry::default();
+        let mut layer = LayerDense::new(3, 2, Box::new(Sigmoid::new()));
+        let record = layer.to_record().unwrap();
+        let mut loaded = LayerDense::from_record(&record, &registry).unwrap();
+        assert_eq!(loaded.forward(&[1., 2., 3.]), layer.forward(&[1., 2., 3.]));
+
+        let layer = LayerDense::new(3, 2, Box::new(Linear::new())).without_bias();
+        let mut loaded = LayerDense::from_record(&layer.to_record().unwrap(), &registry).unwrap();
+        assert_eq!(loaded.parameters().len(), 1);
+    }
 }
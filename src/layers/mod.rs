@@ -1 +1,68 @@
 pub mod dense;
+
+use crate::serialization::{LayerRecord, ModelError};
+use rand::RngCore;
+
+/// A building block of a network. `forward` caches whatever the layer needs so
+/// that a following call to `backwards` can turn the derivatives of the cost
+/// with respect to the outputs into derivatives with respect to the inputs.
+pub trait Layer {
+    fn forward(&mut self, inputs: &[f32]) -> Vec<f32>;
+    fn backwards(&mut self, derivatives: &[f32]) -> Vec<f32>;
+
+    /// How many values `forward` returns.
+    fn outputs(&self) -> usize;
+
+    /// Draws fresh values for every parameter from `rng`, using the
+    /// initializers the layer was built with.
+    fn initialize(&mut self, _rng: &mut dyn RngCore) {}
+
+    /// The trainable values of the layer together with the gradients
+    /// accumulated by `backwards` since they were last zeroed. The order must
+    /// be the same on every call so optimizers can keep state per parameter.
+    fn parameters(&mut self) -> Vec<Parameter<'_>> {
+        vec![]
+    }
+
+    /// The layers this one is made of, for containers such as `Sequential`.
+    fn sublayers(&mut self) -> Vec<&mut dyn Layer> {
+        vec![]
+    }
+
+    fn zero_gradients(&mut self) {
+        for parameter in self.parameters() {
+            for gradient in parameter.gradients.iter_mut() {
+                *gradient = 0.;
+            }
+        }
+    }
+
+    /// Describes the layer for saving. Layers that can't be saved keep the
+    /// default, which makes saving the model fail.
+    fn to_record(&self) -> Result<LayerRecord, ModelError> {
+        Err(ModelError::Unsupported(
+            "layer does not implement to_record".to_string(),
+        ))
+    }
+}
+
+/// The dense layer under the name used when building a `Sequential`.
+pub type Dense = dense::LayerDense;
+
+/// A softmax over the outputs of the layer before it in a `Sequential`,
+/// which gives it its size, so it can't be the first layer:
+///
+/// ```
+/// use rann::activations::Relu;
+/// use rann::layers::{Dense, Softmax};
+/// use rann::sequential::Sequential;
+///
+/// let model = Sequential::new().add(Dense::new(784, 32, Relu)).add(Softmax);
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Softmax;
+
+pub struct Parameter<'a> {
+    pub values: &'a mut [f32],
+    pub gradients: &'a mut [f32],
+}
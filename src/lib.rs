@@ -1,9 +1,28 @@
-mod activations;
-mod layers;
-mod math;
+pub mod activations;
+pub mod callbacks;
+pub mod data;
+pub mod gradcheck;
+pub mod initializers;
+pub mod layers;
+pub mod losses;
+pub mod math;
+pub mod metrics;
+#[cfg(test)]
 mod mnist;
-mod node;
+pub mod node;
+pub mod optimizers;
+pub mod pipeline;
+pub mod preprocessing;
 pub mod rann;
+pub mod schedulers;
+pub mod sequential;
+pub mod serialization;
+pub mod tensor;
+#[cfg(test)]
+mod testing;
+pub mod training;
 
 extern crate csv;
+extern crate flate2;
 extern crate rand;
+extern crate rand_chacha;
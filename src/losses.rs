@@ -0,0 +1,343 @@
+use crate::math::mean_squared_error;
+
+const EPSILON: f32 = 1e-7;
+
+pub trait Loss {
+    fn compute(&self, predicted: &[f32], target: &[f32]) -> f32;
+    /// Derivative of the loss with respect to each predicted value.
+    fn compute_derivative(&self, predicted: &[f32], target: &[f32]) -> Vec<f32>;
+}
+
+#[derive(Default)]
+pub struct MeanSquaredError {}
+#[derive(Default)]
+pub struct MeanAbsoluteError {}
+#[derive(Default)]
+pub struct CategoricalCrossEntropy {}
+#[derive(Default)]
+pub struct BinaryCrossEntropy {}
+pub struct Huber {
+    delta: f32,
+}
+#[derive(Default)]
+pub struct Hinge {}
+
+impl MeanSquaredError {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl MeanAbsoluteError {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl CategoricalCrossEntropy {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl BinaryCrossEntropy {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Huber {
+    pub fn new(delta: f32) -> Self {
+        assert!(delta > 0.);
+        Self { delta }
+    }
+}
+
+impl Default for Huber {
+    fn default() -> Self {
+        Self::new(1.)
+    }
+}
+
+impl Hinge {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Loss for MeanSquaredError {
+    fn compute(&self, predicted: &[f32], target: &[f32]) -> f32 {
+        mean_squared_error(predicted, target)
+    }
+
+    fn compute_derivative(&self, predicted: &[f32], target: &[f32]) -> Vec<f32> {
+        let size = predicted.len() as f32;
+        predicted
+            .iter()
+            .zip(target)
+            .map(|(p, t)| 2. * (p - t) / size)
+            .collect()
+    }
+}
+
+impl Loss for MeanAbsoluteError {
+    fn compute(&self, predicted: &[f32], target: &[f32]) -> f32 {
+        if predicted.is_empty() {
+            return 0.;
+        }
+        let error: f32 = predicted
+            .iter()
+            .zip(target)
+            .map(|(p, t)| (p - t).abs())
+            .sum();
+        error / predicted.len() as f32
+    }
+
+    fn compute_derivative(&self, predicted: &[f32], target: &[f32]) -> Vec<f32> {
+        let size = predicted.len() as f32;
+        predicted
+            .iter()
+            .zip(target)
+            .map(|(p, t)| {
+                if p == t {
+                    return 0.;
+                }
+                (p - t).signum() / size
+            })
+            .collect()
+    }
+}
+
+impl Loss for CategoricalCrossEntropy {
+    fn compute(&self, predicted: &[f32], target: &[f32]) -> f32 {
+        -predicted
+            .iter()
+            .zip(target)
+            .map(|(p, t)| t * p.max(EPSILON).ln())
+            .sum::<f32>()
+    }
+
+    fn compute_derivative(&self, predicted: &[f32], target: &[f32]) -> Vec<f32> {
+        predicted
+            .iter()
+            .zip(target)
+            .map(|(p, t)| -t / p.max(EPSILON))
+            .collect()
+    }
+}
+
+impl Loss for BinaryCrossEntropy {
+    fn compute(&self, predicted: &[f32], target: &[f32]) -> f32 {
+        if predicted.is_empty() {
+            return 0.;
+        }
+        let error: f32 = predicted
+            .iter()
+            .zip(target)
+            .map(|(p, t)| {
+                let p = p.clamp(EPSILON, 1. - EPSILON);
+                -(t * p.ln() + (1. - t) * (1. - p).ln())
+            })
+            .sum();
+        error / predicted.len() as f32
+    }
+
+    fn compute_derivative(&self, predicted: &[f32], target: &[f32]) -> Vec<f32> {
+        let size = predicted.len() as f32;
+        predicted
+            .iter()
+            .zip(target)
+            .map(|(p, t)| {
+                let p = p.clamp(EPSILON, 1. - EPSILON);
+                (p - t) / (p * (1. - p)) / size
+            })
+            .collect()
+    }
+}
+
+impl Loss for Huber {
+    fn compute(&self, predicted: &[f32], target: &[f32]) -> f32 {
+        if predicted.is_empty() {
+            return 0.;
+        }
+        let error: f32 = predicted
+            .iter()
+            .zip(target)
+            .map(|(p, t)| {
+                let error = (p - t).abs();
+                if error <= self.delta {
+                    0.5 * error.powi(2)
+                } else {
+                    self.delta * (error - 0.5 * self.delta)
+                }
+            })
+            .sum();
+        error / predicted.len() as f32
+    }
+
+    fn compute_derivative(&self, predicted: &[f32], target: &[f32]) -> Vec<f32> {
+        let size = predicted.len() as f32;
+        predicted
+            .iter()
+            .zip(target)
+            .map(|(p, t)| {
+                let error = p - t;
+                if error.abs() <= self.delta {
+                    error / size
+                } else {
+                    self.delta * error.signum() / size
+                }
+            })
+            .collect()
+    }
+}
+
+impl Hinge {
+    // Index of the target class and of the highest scoring other class.
+    fn classes(predicted: &[f32], target: &[f32]) -> (usize, usize) {
+        let mut true_class = 0;
+        for (i, t) in target.iter().enumerate() {
+            if *t > target[true_class] {
+                true_class = i;
+            }
+        }
+        let mut rival = None;
+        for (i, p) in predicted.iter().enumerate() {
+            if i == true_class {
+                continue;
+            }
+            match rival {
+                Some(j) if predicted[j] >= *p => {}
+                _ => rival = Some(i),
+            }
+        }
+        (true_class, rival.unwrap_or(true_class))
+    }
+}
+
+impl Loss for Hinge {
+    fn compute(&self, predicted: &[f32], target: &[f32]) -> f32 {
+        if predicted.len() < 2 {
+            return 0.;
+        }
+        let (true_class, rival) = Self::classes(predicted, target);
+        (1. + predicted[rival] - predicted[true_class]).max(0.)
+    }
+
+    fn compute_derivative(&self, predicted: &[f32], target: &[f32]) -> Vec<f32> {
+        let mut derivatives = vec![0.; predicted.len()];
+        if self.compute(predicted, target) > 0. {
+            let (true_class, rival) = Self::classes(predicted, target);
+            derivatives[true_class] = -1.;
+            derivatives[rival] = 1.;
+        }
+        derivatives
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_close(res: &[f32], expected: &[f32]) {
+        assert_eq!(res.len(), expected.len());
+        for (r, e) in res.iter().zip(expected) {
+            assert!((r - e).abs() < 1e-4, "{:?} != {:?}", res, expected);
+        }
+    }
+
+    // Compares the analytic derivative against a central difference.
+    fn check_derivative(loss: &dyn Loss, predicted: &[f32], target: &[f32]) {
+        let h = 1e-3;
+        let derivatives = loss.compute_derivative(predicted, target);
+        for i in 0..predicted.len() {
+            let mut plus = predicted.to_vec();
+            plus[i] += h;
+            let mut minus = predicted.to_vec();
+            minus[i] -= h;
+            let numeric = (loss.compute(&plus, target) - loss.compute(&minus, target)) / (2. * h);
+            assert!((numeric - derivatives[i]).abs() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn mse() {
+        let mse = MeanSquaredError::new();
+        assert_eq!(mse.compute(&[1., -1.], &[0., -5.]), 8.5);
+        assert_close(&mse.compute_derivative(&[1., -1.], &[0., -5.]), &[1., 4.]);
+        check_derivative(&mse, &[0.3, 0.9], &[0.1, 1.2]);
+    }
+
+    #[test]
+    fn mae() {
+        let mae = MeanAbsoluteError::new();
+        assert_eq!(mae.compute(&[1., -1.], &[0., -5.]), 2.5);
+        assert_close(&mae.compute_derivative(&[1., -1.], &[0., -5.]), &[0.5, 0.5]);
+        check_derivative(&mae, &[0.3, 0.9], &[0.1, 1.2]);
+    }
+
+    #[test]
+    fn categorical_cross_entropy() {
+        let cce = CategoricalCrossEntropy::new();
+        assert!((cce.compute(&[0.25, 0.75], &[0., 1.]) - 0.2877).abs() < 1e-4);
+        assert_close(
+            &cce.compute_derivative(&[0.25, 0.75], &[0., 1.]),
+            &[0., -1.3333],
+        );
+        assert!(cce.compute(&[1., 0.], &[0., 1.]).is_finite());
+        check_derivative(&cce, &[0.2, 0.3, 0.5], &[0., 0., 1.]);
+    }
+
+    #[test]
+    fn binary_cross_entropy() {
+        let bce = BinaryCrossEntropy::new();
+        assert!((bce.compute(&[0.8], &[1.]) - 0.2231).abs() < 1e-4);
+        assert!((bce.compute(&[0.8], &[0.]) - 1.6094).abs() < 1e-4);
+        assert!(bce.compute(&[0.], &[1.]).is_finite());
+        check_derivative(&bce, &[0.8, 0.3], &[1., 0.]);
+    }
+
+    #[test]
+    fn huber() {
+        let huber = Huber::new(1.);
+        assert_eq!(huber.compute(&[0.5], &[0.]), 0.125);
+        assert_eq!(huber.compute(&[3.], &[0.]), 2.5);
+        assert_close(
+            &huber.compute_derivative(&[0.5, 3.], &[0., 0.]),
+            &[0.25, 0.5],
+        );
+        check_derivative(&huber, &[0.4, 2.5], &[0., 0.]);
+    }
+
+    #[test]
+    fn hinge() {
+        let hinge = Hinge::new();
+        assert_eq!(hinge.compute(&[3., 1., 0.5], &[1., 0., 0.]), 0.);
+        assert_eq!(hinge.compute(&[1., 1.5, 0.5], &[1., 0., 0.]), 1.5);
+        assert_close(
+            &hinge.compute_derivative(&[1., 1.5, 0.5], &[1., 0., 0.]),
+            &[-1., 1., 0.],
+        );
+        check_derivative(&hinge, &[1., 1.5, 0.5], &[1., 0., 0.]);
+    }
+
+    struct HalfSquared {}
+
+    impl Loss for HalfSquared {
+        fn compute(&self, predicted: &[f32], target: &[f32]) -> f32 {
+            0.5 * (predicted[0] - target[0]).powi(2)
+        }
+
+        fn compute_derivative(&self, predicted: &[f32], target: &[f32]) -> Vec<f32> {
+            vec![predicted[0] - target[0]]
+        }
+    }
+
+    #[test]
+    fn custom_loss() {
+        let losses: Vec<Box<dyn Loss>> = vec![Box::new(HalfSquared {}), Box::new(Huber::default())];
+        for loss in losses.iter() {
+            assert_eq!(loss.compute(&[0.5], &[0.]), 0.125);
+        }
+    }
+}
@@ -1,37 +1,13 @@
 use std::cmp::Ordering;
 
-pub fn dot(vec1: &Vec<f32>, vec2: &Vec<f32>) -> f32 {
+pub fn dot(vec1: &[f32], vec2: &[f32]) -> f32 {
     vec1.iter().zip(vec2.iter()).map(|(x, y)| x * y).sum()
 }
 
-pub fn matrix_multiply_vec(mat: &Vec<Vec<f32>>, vec: &Vec<f32>) -> Vec<f32> {
-    let mut res = vec![];
-    for row in mat.iter() {
-        res.push(dot(row, vec));
-    }
-    res
-}
-
-pub fn mat_multiply_mat(vec: &Vec<Vec<f32>>, mat: &Vec<Vec<f32>>) -> Vec<Vec<f32>> {
-    let mut res = vec![vec![0.; mat[0].len()]; vec.len()];
-    for i in 0..res.len() {
-        for j in 0..res[0].len() {
-            let col_mat_2 = mat.iter().map(|row| row[j]);
-            res[i][j] = vec[i]
-                .iter()
-                .zip(col_mat_2)
-                .map(|(x, y)| x * y)
-                .sum::<f32>();
-        }
-    }
-
-    res
-}
-
 pub fn cut_val(val: f32, max: f32) -> f32 {
     if val > max {
         max
-    } else if -max < val {
+    } else if val < -max {
         -max
     } else {
         val
@@ -44,7 +20,7 @@ pub fn one_hot_encode(i: usize, size: usize) -> Vec<usize> {
     one_hot_encoded
 }
 
-pub fn mean_squared_error(vec1: &Vec<f32>, vec2: &Vec<f32>) -> f32 {
+pub fn mean_squared_error(vec1: &[f32], vec2: &[f32]) -> f32 {
     let size = vec1.len();
     if size == 0 {
         return 0.;
@@ -59,20 +35,18 @@ pub fn mean_squared_error(vec1: &Vec<f32>, vec2: &Vec<f32>) -> f32 {
     error / size as f32
 }
 
-pub fn softmax(vals: &Vec<f32>) -> Vec<f32> {
-    let e = 2.71828_f32;
+pub fn softmax(vals: &[f32]) -> Vec<f32> {
     let max_val = vals
         .iter()
         .max_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal))
         .unwrap_or(&0.);
-    let vals = vals.iter().map(|val| e.powf(*val - max_val));
+    let vals = vals.iter().map(|val| (*val - max_val).exp());
     let denominator: f32 = vals.clone().sum();
     vals.map(|val| val / denominator).collect()
 }
 
-pub fn softmax_derivative(vals: &Vec<f32>) -> Vec<f32> {
-    let e = 2.71828_f32;
-    let vals: Vec<f32> = vals.iter().map(|val| e.powf(*val)).collect();
+pub fn softmax_derivative(vals: &[f32]) -> Vec<f32> {
+    let vals: Vec<f32> = vals.iter().map(|val| val.exp()).collect();
     let mut dervs = vec![0.; vals.len()];
     let total: f32 = vals.iter().sum();
     let total_square = total.powi(2);
@@ -89,32 +63,26 @@ mod test {
 
     #[test]
     fn dot_product() {
-        assert_eq!(dot(&vec![1.0], &vec![1.0]), 1.0);
-        assert_eq!(dot(&vec![1.0, 2.0], &vec![1.0, 2.0]), 5.0);
-        assert_eq!(dot(&vec![], &vec![]), 0.);
-        assert_eq!(dot(&vec![-1.], &vec![-1.]), 1.);
-        assert_eq!(dot(&vec![-1., 1., 2.], &vec![-1., 1., 2.]), 6.);
+        assert_eq!(dot(&[1.0], &[1.0]), 1.0);
+        assert_eq!(dot(&[1.0, 2.0], &[1.0, 2.0]), 5.0);
+        assert_eq!(dot(&[], &[]), 0.);
+        assert_eq!(dot(&[-1.], &[-1.]), 1.);
+        assert_eq!(dot(&[-1., 1., 2.], &[-1., 1., 2.]), 6.);
     }
 
     #[test]
-    fn mean_squared() {
-        assert_eq!(mean_squared_error(&vec![], &vec![]), 0.);
-        assert_eq!(mean_squared_error(&vec![1., -4.], &vec![1., -4.]), 0.);
-        assert_eq!(mean_squared_error(&vec![1., -1.], &vec![0., -5.]), 8.5);
+    fn cut_values() {
+        assert_eq!(cut_val(3., 2.), 2.);
+        assert_eq!(cut_val(-3., 2.), -2.);
+        assert_eq!(cut_val(0.5, 2.), 0.5);
+        assert_eq!(cut_val(-0.5, 2.), -0.5);
     }
 
     #[test]
-    fn vec_mult_mat() {
-        let res = mat_multiply_mat(&vec![vec![4.], vec![6.]], &vec![vec![-2., 5.]]);
-        assert_eq!(res[0], vec![-8., 20.]);
-        assert_eq!(res[1], vec![-12., 30.]);
-
-        let res = mat_multiply_mat(
-            &vec![vec![4., -2., 2.], vec![1., 5., -9.]],
-            &vec![vec![12., -5.], vec![-3., -2.], vec![4., 2.]],
-        );
-        assert_eq!(res[0], vec![62., -12.]);
-        assert_eq!(res[1], vec![-39., -33.]);
+    fn mean_squared() {
+        assert_eq!(mean_squared_error(&[], &[]), 0.);
+        assert_eq!(mean_squared_error(&[1., -4.], &[1., -4.]), 0.);
+        assert_eq!(mean_squared_error(&[1., -1.], &[0., -5.]), 8.5);
     }
 
     #[test]
@@ -0,0 +1,434 @@
+//! Threshold sweeping evaluation of binary classifiers, and of multi-class
+//! classifiers one class against the rest.
+
+use crate::metrics::{target_class, Average, Metric};
+
+/// Scores of one class against the rest: the predicted probability of
+/// `class` for every sample and whether the sample really is of that class.
+/// Single-output predictions are binary, so class 1 is the output itself and
+/// class 0 is one minus it. Targets are read as by `metrics::target_class`.
+pub fn one_vs_rest(
+    predictions: &[Vec<f32>],
+    targets: &[Vec<f32>],
+    class: usize,
+) -> (Vec<f32>, Vec<bool>) {
+    predictions
+        .iter()
+        .zip(targets)
+        .map(|(prediction, target)| {
+            let score = match (prediction.len(), class) {
+                (1, 0) => 1. - prediction[0],
+                (1, _) => prediction[0],
+                _ => prediction[class],
+            };
+            (score, target_class(prediction, target) == class)
+        })
+        .unzip()
+}
+
+// True and false positive counts when everything scoring at least the
+// threshold is called positive, for every distinct score from high to low.
+// Scores are ordered by `f32::total_cmp`, which puts NaN at one end rather
+// than panicking.
+fn sweep(scores: &[f32], labels: &[bool]) -> Vec<(f32, usize, usize)> {
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by(|a, b| scores[*b].total_cmp(&scores[*a]));
+    let mut points: Vec<(f32, usize, usize)> = vec![];
+    let (mut tp, mut fp) = (0, 0);
+    for (i, index) in order.iter().enumerate() {
+        if labels[*index] {
+            tp += 1;
+        } else {
+            fp += 1;
+        }
+        let last_of_score = order
+            .get(i + 1)
+            .map_or(true, |next| scores[*next] != scores[*index]);
+        if last_of_score {
+            points.push((scores[*index], tp, fp));
+        }
+    }
+    points
+}
+
+fn positives(labels: &[bool]) -> (usize, usize) {
+    let positive = labels.iter().filter(|label| **label).count();
+    (positive, labels.len() - positive)
+}
+
+/// Receiver operating characteristic: true and false positive rates for
+/// every threshold, from high to low. The first point is (0, 0) with an
+/// infinite threshold.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RocCurve {
+    pub thresholds: Vec<f32>,
+    pub false_positive_rates: Vec<f32>,
+    pub true_positive_rates: Vec<f32>,
+}
+
+impl RocCurve {
+    /// Rates are NaN if `labels` lacks positives or negatives.
+    pub fn new(scores: &[f32], labels: &[bool]) -> Self {
+        let (positive, negative) = positives(labels);
+        let mut curve = Self {
+            thresholds: vec![f32::INFINITY],
+            false_positive_rates: vec![0.],
+            true_positive_rates: vec![0.],
+        };
+        for (threshold, tp, fp) in sweep(scores, labels) {
+            curve.thresholds.push(threshold);
+            curve.false_positive_rates.push(fp as f32 / negative as f32);
+            curve.true_positive_rates.push(tp as f32 / positive as f32);
+        }
+        curve
+    }
+
+    /// Area under the curve by the trapezoidal rule.
+    pub fn auc(&self) -> f32 {
+        let x = &self.false_positive_rates;
+        let y = &self.true_positive_rates;
+        (1..x.len())
+            .map(|i| (x[i] - x[i - 1]) * (y[i] + y[i - 1]) / 2.)
+            .sum()
+    }
+
+    /// The threshold maximising Youden's J, the true minus the false
+    /// positive rate.
+    pub fn best_threshold(&self) -> Threshold {
+        let points = (1..self.thresholds.len()).map(|i| Threshold {
+            value: self.thresholds[i],
+            score: self.true_positive_rates[i] - self.false_positive_rates[i],
+        });
+        best(points)
+    }
+}
+
+/// Precision and recall for every threshold, from high to low.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrCurve {
+    pub thresholds: Vec<f32>,
+    pub precisions: Vec<f32>,
+    pub recalls: Vec<f32>,
+}
+
+impl PrCurve {
+    pub fn new(scores: &[f32], labels: &[bool]) -> Self {
+        let (positive, _) = positives(labels);
+        let mut curve = Self {
+            thresholds: vec![],
+            precisions: vec![],
+            recalls: vec![],
+        };
+        for (threshold, tp, fp) in sweep(scores, labels) {
+            curve.thresholds.push(threshold);
+            curve.precisions.push(tp as f32 / (tp + fp) as f32);
+            curve.recalls.push(tp as f32 / positive.max(1) as f32);
+        }
+        curve
+    }
+
+    /// Precision at every threshold weighted by the recall it adds, without
+    /// interpolation.
+    pub fn average_precision(&self) -> f32 {
+        let mut previous = 0.;
+        let mut sum = 0.;
+        for (precision, recall) in self.precisions.iter().zip(self.recalls.iter()) {
+            sum += (recall - previous) * precision;
+            previous = *recall;
+        }
+        sum
+    }
+
+    /// The threshold with the highest F1 score.
+    pub fn best_threshold(&self) -> Threshold {
+        let points = (0..self.thresholds.len()).map(|i| {
+            let (p, r) = (self.precisions[i], self.recalls[i]);
+            Threshold {
+                value: self.thresholds[i],
+                score: if p + r > 0. { 2. * p * r / (p + r) } else { 0. },
+            }
+        });
+        best(points)
+    }
+}
+
+/// A decision threshold and the score it reached.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Threshold {
+    pub value: f32,
+    pub score: f32,
+}
+
+// The highest scoring point, the first one on ties.
+fn best(points: impl Iterator<Item = Threshold>) -> Threshold {
+    points.fold(
+        Threshold {
+            value: f32::NAN,
+            score: f32::NEG_INFINITY,
+        },
+        |best, point| {
+            if point.score > best.score {
+                point
+            } else {
+                best
+            }
+        },
+    )
+}
+
+/// How to pick a decision threshold.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Criterion {
+    F1,
+    YoudensJ,
+}
+
+pub fn best_threshold(scores: &[f32], labels: &[bool], criterion: Criterion) -> Threshold {
+    match criterion {
+        Criterion::F1 => PrCurve::new(scores, labels).best_threshold(),
+        Criterion::YoudensJ => RocCurve::new(scores, labels).best_threshold(),
+    }
+}
+
+pub fn roc_auc(scores: &[f32], labels: &[bool]) -> f32 {
+    RocCurve::new(scores, labels).auc()
+}
+
+pub fn average_precision(scores: &[f32], labels: &[bool]) -> f32 {
+    PrCurve::new(scores, labels).average_precision()
+}
+
+// `score` for every class one-vs-rest, combined by `average`. Binary
+// predictions only score the positive class. Classes without positive
+// samples are left out, as are classes scoring NaN, such as the ROC AUC of a
+// class without negative samples, so the result is NaN when no class is left.
+fn averaged(
+    predictions: &[Vec<f32>],
+    targets: &[Vec<f32>],
+    average: Average,
+    score: fn(&[f32], &[bool]) -> f32,
+) -> f32 {
+    let width = predictions.first().map_or(0, |p| p.len());
+    let classes: Vec<usize> = match width {
+        1 => vec![1],
+        _ => (0..width).collect(),
+    };
+    let mut total = 0.;
+    let mut weights = 0.;
+    for class in classes {
+        let (scores, labels) = one_vs_rest(predictions, targets, class);
+        let (positive, _) = positives(&labels);
+        if positive == 0 {
+            continue;
+        }
+        let value = score(&scores, &labels);
+        if value.is_nan() {
+            continue;
+        }
+        let weight = match average {
+            Average::Weighted => positive as f32,
+            _ => 1.,
+        };
+        total += weight * value;
+        weights += weight;
+    }
+    total / weights
+}
+
+/// Area under the ROC curve; macro averaged over classes one-vs-rest for
+/// multi-class predictions. Classes without both positive and negative
+/// samples are left out of the average, and the result is NaN when no class
+/// has both.
+pub struct RocAuc {
+    average: Average,
+}
+
+impl RocAuc {
+    pub fn new() -> Self {
+        Self {
+            average: Average::Macro,
+        }
+    }
+
+    /// Weighs every class by its number of samples instead.
+    pub fn weighted() -> Self {
+        Self {
+            average: Average::Weighted,
+        }
+    }
+}
+
+impl Default for RocAuc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metric for RocAuc {
+    fn name(&self) -> &str {
+        "roc_auc"
+    }
+
+    fn compute(&self, predictions: &[Vec<f32>], targets: &[Vec<f32>]) -> f32 {
+        averaged(predictions, targets, self.average, roc_auc)
+    }
+}
+
+/// Average precision, macro averaged over classes one-vs-rest for
+/// multi-class predictions. Classes without positive or without negative
+/// samples are left out of the average, and the result is NaN when no class
+/// has both.
+#[derive(Default)]
+pub struct AveragePrecision {}
+
+impl AveragePrecision {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Metric for AveragePrecision {
+    fn name(&self) -> &str {
+        "average_precision"
+    }
+
+    fn compute(&self, predictions: &[Vec<f32>], targets: &[Vec<f32>]) -> f32 {
+        averaged(predictions, targets, Average::Macro, average_precision)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::close;
+
+    #[test]
+    fn roc() {
+        let scores = [0.1, 0.4, 0.35, 0.8];
+        let labels = [false, false, true, true];
+        let roc = RocCurve::new(&scores, &labels);
+        assert_eq!(roc.thresholds[1..], [0.8, 0.4, 0.35, 0.1]);
+        assert_eq!(roc.false_positive_rates, vec![0., 0., 0.5, 0.5, 1.]);
+        assert_eq!(roc.true_positive_rates, vec![0., 0.5, 0.5, 1., 1.]);
+        assert!(close(roc.auc(), 0.75));
+        assert_eq!(
+            roc.best_threshold(),
+            Threshold {
+                value: 0.8,
+                score: 0.5
+            }
+        );
+        assert!(close(
+            roc_auc(&[0.1, 0.2, 0.8, 0.9], &[false, false, true, true]),
+            1.
+        ));
+    }
+
+    #[test]
+    fn roc_ties() {
+        let roc = RocCurve::new(&[0.5, 0.5, 0.5, 0.5], &[true, false, true, false]);
+        assert_eq!(roc.thresholds.len(), 2);
+        assert!(close(roc.auc(), 0.5));
+        assert!(roc_auc(&[0.2, 0.3], &[true, true]).is_nan());
+    }
+
+    #[test]
+    fn precision_recall() {
+        let scores = [0.1, 0.4, 0.35, 0.8];
+        let labels = [false, false, true, true];
+        let pr = PrCurve::new(&scores, &labels);
+        assert_eq!(pr.precisions, vec![1., 0.5, 2. / 3., 0.5]);
+        assert_eq!(pr.recalls, vec![0.5, 0.5, 1., 1.]);
+        assert!(close(pr.average_precision(), 0.833_333_3));
+
+        let best = best_threshold(&scores, &labels, Criterion::F1);
+        assert_eq!(best.value, 0.35);
+        assert!(close(best.score, 0.8));
+        let best = best_threshold(&scores, &labels, Criterion::YoudensJ);
+        assert_eq!(best.value, 0.8);
+    }
+
+    #[test]
+    fn nan_scores() {
+        let scores = [0.1, f32::NAN, 0.35, 0.8];
+        let labels = [false, false, true, true];
+        assert_eq!(RocCurve::new(&scores, &labels).thresholds.len(), 5);
+        assert!(RocAuc::new()
+            .compute(&[vec![0.2], vec![0.4]], &[vec![0.], vec![0.]])
+            .is_nan());
+    }
+
+    #[test]
+    fn classes_without_negatives() {
+        // every sample is of class 1, which has no negatives
+        let predictions = vec![vec![0.2], vec![0.7], vec![0.9]];
+        let targets = vec![vec![1.]; 3];
+        let (_, labels) = one_vs_rest(&predictions, &targets, 1);
+        assert!(roc_auc(&[0.2, 0.7, 0.9], &labels).is_nan());
+        assert!(RocAuc::new().compute(&predictions, &targets).is_nan());
+        assert!(close(
+            AveragePrecision::new().compute(&predictions, &targets),
+            1.
+        ));
+
+        let predictions = vec![vec![0.6, 0.3, 0.1], vec![0.5, 0.3, 0.2]];
+        let targets = vec![vec![0.], vec![0.]];
+        assert!(RocAuc::weighted().compute(&predictions, &targets).is_nan());
+    }
+
+    #[test]
+    fn one_vs_rest_metrics() {
+        let predictions = vec![vec![0.9], vec![0.3], vec![0.6], vec![0.2]];
+        let targets = vec![vec![1.], vec![1.], vec![0.], vec![0.]];
+        let (scores, labels) = one_vs_rest(&predictions, &targets, 0);
+        assert!(close(scores[0], 0.1));
+        assert_eq!(labels, vec![false, false, true, true]);
+        assert!(close(RocAuc::new().compute(&predictions, &targets), 0.75));
+
+        let predictions = vec![
+            vec![0.7, 0.2, 0.1],
+            vec![0.2, 0.5, 0.3],
+            vec![0.3, 0.3, 0.4],
+            vec![0.1, 0.2, 0.7],
+        ];
+        let targets = vec![
+            vec![1., 0., 0.],
+            vec![0., 1., 0.],
+            vec![0., 1., 0.],
+            vec![0., 0., 1.],
+        ];
+        // classes 0 and 2 are separated perfectly; class 1 scores
+        // [0.2, 0.5, 0.3, 0.2] with positives 0.5 and 0.3, also perfect
+        assert!(close(RocAuc::new().compute(&predictions, &targets), 1.));
+        assert!(close(
+            AveragePrecision::new().compute(&predictions, &targets),
+            1.
+        ));
+
+        let targets = vec![
+            vec![1., 0., 0.],
+            vec![0., 1., 0.],
+            vec![0., 0., 1.],
+            vec![0., 0., 1.],
+        ];
+        let class_2 = {
+            let (scores, labels) = one_vs_rest(&predictions, &targets, 2);
+            roc_auc(&scores, &labels)
+        };
+        assert!(close(class_2, 1.));
+        let class_1 = {
+            let (scores, labels) = one_vs_rest(&predictions, &targets, 1);
+            roc_auc(&scores, &labels)
+        };
+        let indices = vec![vec![0.], vec![1.], vec![2.], vec![2.]];
+        assert_eq!(
+            one_vs_rest(&predictions, &indices, 2),
+            one_vs_rest(&predictions, &targets, 2)
+        );
+        let weighted = (1. + class_1 + 2. * class_2) / 4.;
+        assert!(close(
+            RocAuc::weighted().compute(&predictions, &targets),
+            weighted
+        ));
+    }
+}
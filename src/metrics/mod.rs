@@ -0,0 +1,599 @@
+pub mod curves;
+pub mod regression;
+
+use std::fmt;
+
+/// A score computed over the predictions for a whole dataset, such as the
+/// accuracy reported by `fit`.
+pub trait Metric {
+    fn name(&self) -> &str;
+    fn compute(&self, predictions: &[Vec<f32>], targets: &[Vec<f32>]) -> f32;
+}
+
+/// Index of the largest value. Ties go to the first one.
+pub fn argmax(values: &[f32]) -> usize {
+    let mut best = 0;
+    for (i, value) in values.iter().enumerate() {
+        if *value > values[best] {
+            best = i;
+        }
+    }
+    best
+}
+
+/// The class a prediction or one-hot target stands for. Outputs with a single
+/// value are treated as binary and thresholded at 0.5, anything wider is
+/// compared by argmax.
+pub fn class_of(values: &[f32]) -> usize {
+    match values.len() {
+        1 => (values[0] >= 0.5) as usize,
+        _ => argmax(values),
+    }
+}
+
+/// The class of `target` when scored against `prediction`. A target as wide
+/// as the prediction goes through `class_of`. A single value next to a wider
+/// prediction is a class index, as with `mnist::Labels::Index`.
+///
+/// Panics when the widths don't fit together or the index is not one of the
+/// predicted classes, rather than scoring the wrong output.
+pub fn target_class(prediction: &[f32], target: &[f32]) -> usize {
+    match (prediction.len(), target.len()) {
+        (width, 1) if width > 1 => {
+            let class = target[0];
+            assert!(
+                class >= 0. && class.fract() == 0. && (class as usize) < width,
+                "target class {} is not one of the {} predicted classes",
+                class,
+                width
+            );
+            class as usize
+        }
+        (width, target_width) => {
+            assert_eq!(
+                width, target_width,
+                "predictions have {} values but targets have {}",
+                width, target_width
+            );
+            class_of(target)
+        }
+    }
+}
+
+// Panics unless there is one target per prediction.
+fn assert_paired(predictions: &[Vec<f32>], targets: &[Vec<f32>]) {
+    assert_eq!(
+        predictions.len(),
+        targets.len(),
+        "{} predictions but {} targets",
+        predictions.len(),
+        targets.len()
+    );
+}
+
+/// Number of classes predictions of this width stand for.
+fn class_count(width: usize) -> usize {
+    width.max(2)
+}
+
+/// Share of samples whose predicted class, see `class_of`, matches the
+/// target, see `target_class`. Panics unless there is one target per
+/// prediction.
+#[derive(Default)]
+pub struct Accuracy {}
+
+impl Accuracy {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Metric for Accuracy {
+    fn name(&self) -> &str {
+        "accuracy"
+    }
+
+    fn compute(&self, predictions: &[Vec<f32>], targets: &[Vec<f32>]) -> f32 {
+        assert_paired(predictions, targets);
+        let correct = predictions
+            .iter()
+            .zip(targets)
+            .filter(|(prediction, target)| class_of(prediction) == target_class(prediction, target))
+            .count();
+        correct as f32 / predictions.len().max(1) as f32
+    }
+}
+
+/// Share of samples whose target class, see `target_class`, is among the `k`
+/// highest outputs. Panics unless there is one target per prediction.
+pub struct TopKAccuracy {
+    k: usize,
+    name: String,
+}
+
+impl TopKAccuracy {
+    pub fn new(k: usize) -> Self {
+        Self {
+            k,
+            name: format!("top_{}_accuracy", k),
+        }
+    }
+}
+
+impl Metric for TopKAccuracy {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn compute(&self, predictions: &[Vec<f32>], targets: &[Vec<f32>]) -> f32 {
+        assert_paired(predictions, targets);
+        let correct = predictions
+            .iter()
+            .zip(targets)
+            .filter(|(prediction, target)| {
+                let class = target_class(prediction, target);
+                let score = prediction[class];
+                // classes scoring higher, with ties going to the lower index
+                let ahead = prediction
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, value)| **value > score || (**value == score && *i < class))
+                    .count();
+                ahead < self.k
+            })
+            .count();
+        correct as f32 / predictions.len().max(1) as f32
+    }
+}
+
+/// How per-class scores are combined into one number. `Micro` pools the
+/// counts of all classes, `Macro` is the plain mean over classes and
+/// `Weighted` weighs each class by its number of samples.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Average {
+    Macro,
+    Micro,
+    Weighted,
+}
+
+impl Average {
+    fn name(&self) -> &str {
+        match self {
+            Average::Macro => "macro",
+            Average::Micro => "micro",
+            Average::Weighted => "weighted",
+        }
+    }
+}
+
+/// Counts of actual (rows) against predicted (columns) classes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfusionMatrix {
+    counts: Vec<Vec<usize>>,
+    labels: Option<Vec<String>>,
+}
+
+impl ConfusionMatrix {
+    pub fn new(classes: usize) -> Self {
+        Self {
+            counts: vec![vec![0; classes]; classes],
+            labels: None,
+        }
+    }
+
+    /// Builds the matrix from model outputs and targets, see `class_of` and
+    /// `target_class`. Panics if the predictions differ in width or there isn't
+    /// one target per prediction.
+    pub fn from_predictions(predictions: &[Vec<f32>], targets: &[Vec<f32>]) -> Self {
+        assert_paired(predictions, targets);
+        let width = predictions.first().map_or(0, |p| p.len());
+        let mut matrix = Self::new(class_count(width));
+        for (prediction, target) in predictions.iter().zip(targets) {
+            assert_eq!(
+                prediction.len(),
+                width,
+                "predictions have {} and {} values",
+                width,
+                prediction.len()
+            );
+            matrix.add(target_class(prediction, target), class_of(prediction));
+        }
+        matrix
+    }
+
+    /// Names printed instead of class indices.
+    pub fn with_labels(mut self, labels: &[&str]) -> Self {
+        assert_eq!(labels.len(), self.classes());
+        self.labels = Some(labels.iter().map(|label| label.to_string()).collect());
+        self
+    }
+
+    pub fn add(&mut self, actual: usize, predicted: usize) {
+        self.counts[actual][predicted] += 1;
+    }
+
+    pub fn classes(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn get(&self, actual: usize, predicted: usize) -> usize {
+        self.counts[actual][predicted]
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.iter().flatten().sum()
+    }
+
+    /// Samples whose actual class is `class`.
+    pub fn support(&self, class: usize) -> usize {
+        self.counts[class].iter().sum()
+    }
+
+    fn predicted(&self, class: usize) -> usize {
+        self.counts.iter().map(|row| row[class]).sum()
+    }
+
+    pub fn accuracy(&self) -> f32 {
+        let correct: usize = (0..self.classes()).map(|i| self.counts[i][i]).sum();
+        ratio(correct, self.total())
+    }
+
+    /// Share of the samples predicted as `class` that really are. Zero when
+    /// nothing was predicted as `class`.
+    pub fn precision(&self, class: usize) -> f32 {
+        ratio(self.counts[class][class], self.predicted(class))
+    }
+
+    /// Share of the samples of `class` that were predicted as such.
+    pub fn recall(&self, class: usize) -> f32 {
+        ratio(self.counts[class][class], self.support(class))
+    }
+
+    pub fn f1(&self, class: usize) -> f32 {
+        f1(self.precision(class), self.recall(class))
+    }
+
+    pub fn precision_score(&self, average: Average) -> f32 {
+        self.average(
+            average,
+            |m, class| m.precision(class),
+            |tp, _, predicted| ratio(tp, predicted),
+        )
+    }
+
+    pub fn recall_score(&self, average: Average) -> f32 {
+        self.average(
+            average,
+            |m, class| m.recall(class),
+            |tp, support, _| ratio(tp, support),
+        )
+    }
+
+    pub fn f1_score(&self, average: Average) -> f32 {
+        self.average(
+            average,
+            |m, class| m.f1(class),
+            |tp, support, predicted| f1(ratio(tp, predicted), ratio(tp, support)),
+        )
+    }
+
+    // `micro` gets the pooled true positives, supports and predictions.
+    fn average(
+        &self,
+        average: Average,
+        score: impl Fn(&Self, usize) -> f32,
+        micro: impl Fn(usize, usize, usize) -> f32,
+    ) -> f32 {
+        let classes = 0..self.classes();
+        match average {
+            Average::Macro => {
+                classes.map(|c| score(self, c)).sum::<f32>() / self.classes().max(1) as f32
+            }
+            Average::Weighted => {
+                let weighted: f32 = classes
+                    .map(|c| score(self, c) * self.support(c) as f32)
+                    .sum();
+                weighted / self.total().max(1) as f32
+            }
+            Average::Micro => {
+                let tp = classes.clone().map(|c| self.counts[c][c]).sum();
+                let support = classes.clone().map(|c| self.support(c)).sum();
+                let predicted = classes.map(|c| self.predicted(c)).sum();
+                micro(tp, support, predicted)
+            }
+        }
+    }
+
+    fn label(&self, class: usize) -> String {
+        match &self.labels {
+            Some(labels) => labels[class].clone(),
+            None => class.to_string(),
+        }
+    }
+}
+
+fn ratio(count: usize, total: usize) -> f32 {
+    match total {
+        0 => 0.,
+        _ => count as f32 / total as f32,
+    }
+}
+
+fn f1(precision: f32, recall: f32) -> f32 {
+    match precision + recall {
+        sum if sum > 0. => 2. * precision * recall / sum,
+        _ => 0.,
+    }
+}
+
+/// Prints the counts as a table with actual classes as rows, followed by
+/// precision, recall, F1 and support per class.
+impl fmt::Display for ConfusionMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let labels: Vec<String> = (0..self.classes()).map(|c| self.label(c)).collect();
+        let width = labels
+            .iter()
+            .map(|label| label.len())
+            .chain(
+                self.counts
+                    .iter()
+                    .flatten()
+                    .map(|count| count.to_string().len()),
+            )
+            .chain(std::iter::once("actual".len()))
+            .max()
+            .unwrap_or(1);
+
+        write!(f, "{:>w$}", "actual", w = width)?;
+        for label in labels.iter() {
+            write!(f, " {:>w$}", label, w = width)?;
+        }
+        writeln!(f)?;
+        for (label, row) in labels.iter().zip(self.counts.iter()) {
+            write!(f, "{:>w$}", label, w = width)?;
+            for count in row {
+                write!(f, " {:>w$}", count, w = width)?;
+            }
+            writeln!(f)?;
+        }
+
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:>w$} {:>9} {:>9} {:>9} {:>9}",
+            "class",
+            "precision",
+            "recall",
+            "f1",
+            "support",
+            w = width
+        )?;
+        for (class, label) in labels.iter().enumerate() {
+            writeln!(
+                f,
+                "{:>w$} {:>9.4} {:>9.4} {:>9.4} {:>9}",
+                label,
+                self.precision(class),
+                self.recall(class),
+                self.f1(class),
+                self.support(class),
+                w = width
+            )?;
+        }
+        write!(f, "accuracy {:.4} of {}", self.accuracy(), self.total())
+    }
+}
+
+/// Precision averaged over classes, for use in `fit`.
+pub struct Precision {
+    average: Average,
+    name: String,
+}
+
+impl Precision {
+    pub fn new(average: Average) -> Self {
+        Self {
+            average,
+            name: format!("{}_precision", average.name()),
+        }
+    }
+}
+
+impl Metric for Precision {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn compute(&self, predictions: &[Vec<f32>], targets: &[Vec<f32>]) -> f32 {
+        ConfusionMatrix::from_predictions(predictions, targets).precision_score(self.average)
+    }
+}
+
+pub struct Recall {
+    average: Average,
+    name: String,
+}
+
+impl Recall {
+    pub fn new(average: Average) -> Self {
+        Self {
+            average,
+            name: format!("{}_recall", average.name()),
+        }
+    }
+}
+
+impl Metric for Recall {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn compute(&self, predictions: &[Vec<f32>], targets: &[Vec<f32>]) -> f32 {
+        ConfusionMatrix::from_predictions(predictions, targets).recall_score(self.average)
+    }
+}
+
+pub struct F1Score {
+    average: Average,
+    name: String,
+}
+
+impl F1Score {
+    pub fn new(average: Average) -> Self {
+        Self {
+            average,
+            name: format!("{}_f1", average.name()),
+        }
+    }
+}
+
+impl Metric for F1Score {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn compute(&self, predictions: &[Vec<f32>], targets: &[Vec<f32>]) -> f32 {
+        ConfusionMatrix::from_predictions(predictions, targets).f1_score(self.average)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::close;
+
+    #[test]
+    fn accuracy() {
+        let predictions = vec![
+            vec![0.1, 0.7, 0.2],
+            vec![0.5, 0.3, 0.2],
+            vec![0.2, 0.2, 0.6],
+        ];
+        let targets = vec![vec![0., 1., 0.], vec![0., 1., 0.], vec![0., 0., 1.]];
+        assert!((Accuracy::new().compute(&predictions, &targets) - 2. / 3.).abs() < 1e-6);
+
+        let predictions = vec![vec![0.9], vec![0.4], vec![0.6], vec![0.1]];
+        let targets = vec![vec![1.], vec![1.], vec![1.], vec![0.]];
+        assert_eq!(Accuracy::new().compute(&predictions, &targets), 0.75);
+    }
+
+    #[test]
+    fn top_k_accuracy() {
+        let predictions = vec![
+            vec![0.5, 0.3, 0.2],
+            vec![0.5, 0.3, 0.2],
+            vec![0.5, 0.3, 0.2],
+            vec![0.4, 0.4, 0.2],
+        ];
+        let targets = vec![
+            vec![1., 0., 0.],
+            vec![0., 1., 0.],
+            vec![0., 0., 1.],
+            vec![0., 1., 0.],
+        ];
+        assert_eq!(TopKAccuracy::new(1).compute(&predictions, &targets), 0.25);
+        assert_eq!(TopKAccuracy::new(2).compute(&predictions, &targets), 0.75);
+        assert_eq!(TopKAccuracy::new(3).compute(&predictions, &targets), 1.);
+        assert_eq!(TopKAccuracy::new(2).name(), "top_2_accuracy");
+
+        let indices = vec![vec![0.], vec![1.], vec![2.], vec![1.]];
+        assert_eq!(TopKAccuracy::new(2).compute(&predictions, &indices), 0.75);
+        assert_eq!(Accuracy::new().compute(&predictions, &indices), 0.25);
+    }
+
+    #[test]
+    #[should_panic(expected = "predictions have 3 values but targets have 2")]
+    fn mismatched_widths() {
+        TopKAccuracy::new(1).compute(&[vec![0.5, 0.3, 0.2]], &[vec![0., 1.]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "2 predictions but 1 targets")]
+    fn mismatched_counts() {
+        Accuracy::new().compute(&[vec![0.5, 0.5], vec![0.2, 0.8]], &[vec![1., 0.]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "target class 7 is not one of the 3 predicted classes")]
+    fn index_out_of_range() {
+        ConfusionMatrix::from_predictions(&[vec![0.5, 0.3, 0.2]], &[vec![7.]]);
+    }
+
+    fn matrix() -> ConfusionMatrix {
+        // actual 0: 3 right, 1 as class 1; actual 1: 2 right;
+        // actual 2: 1 right, 1 as 0, 2 as 1
+        let mut matrix = ConfusionMatrix::new(3);
+        for (actual, predicted, count) in [
+            (0, 0, 3),
+            (0, 1, 1),
+            (1, 1, 2),
+            (2, 2, 1),
+            (2, 0, 1),
+            (2, 1, 2),
+        ]
+        .iter()
+        {
+            for _ in 0..*count {
+                matrix.add(*actual, *predicted);
+            }
+        }
+        matrix
+    }
+
+    #[test]
+    fn confusion_matrix_scores() {
+        let matrix = matrix();
+        assert_eq!(matrix.total(), 10);
+        assert!(close(matrix.accuracy(), 0.6));
+        assert!(close(matrix.precision(0), 0.75));
+        assert!(close(matrix.precision(1), 0.4));
+        assert!(close(matrix.precision(2), 1.));
+        assert!(close(matrix.recall(0), 0.75));
+        assert!(close(matrix.recall(1), 1.));
+        assert!(close(matrix.recall(2), 0.25));
+        assert!(close(matrix.f1(2), 0.4));
+
+        assert!(close(matrix.precision_score(Average::Macro), 2.15 / 3.));
+        assert!(close(matrix.recall_score(Average::Weighted), 0.6));
+        assert!(close(matrix.precision_score(Average::Micro), 0.6));
+        assert!(close(matrix.recall_score(Average::Micro), 0.6));
+        assert!(close(matrix.f1_score(Average::Micro), 0.6));
+        let weighted_f1 = (0.75 * 4. + (0.8 / 1.4) * 2. + 0.4 * 4.) / 10.;
+        assert!(close(matrix.f1_score(Average::Weighted), weighted_f1));
+
+        let empty = ConfusionMatrix::new(2);
+        assert_eq!(empty.precision(0), 0.);
+        assert_eq!(empty.f1_score(Average::Macro), 0.);
+    }
+
+    #[test]
+    fn confusion_matrix_from_predictions() {
+        let predictions = vec![vec![0.2, 0.8], vec![0.6, 0.4], vec![0.3, 0.7]];
+        let targets = vec![vec![0., 1.], vec![0., 1.], vec![1., 0.]];
+        let matrix = ConfusionMatrix::from_predictions(&predictions, &targets);
+        assert_eq!(matrix.get(1, 1), 1);
+        assert_eq!(matrix.get(1, 0), 1);
+        assert_eq!(matrix.get(0, 1), 1);
+        assert!(close(
+            F1Score::new(Average::Macro).compute(&predictions, &targets),
+            matrix.f1_score(Average::Macro)
+        ));
+        assert_eq!(Recall::new(Average::Micro).name(), "micro_recall");
+
+        let binary =
+            ConfusionMatrix::from_predictions(&[vec![0.9], vec![0.2]], &[vec![1.], vec![1.]]);
+        assert_eq!(binary.classes(), 2);
+        assert_eq!(binary.get(1, 0), 1);
+    }
+
+    #[test]
+    fn confusion_matrix_display() {
+        let text = matrix().with_labels(&["cat", "dog", "bird"]).to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "actual    cat    dog   bird");
+        assert_eq!(lines[1], "   cat      3      1      0");
+        assert_eq!(lines[3], "  bird      1      2      1");
+        assert!(lines[7].starts_with("   dog    0.4000    1.0000"));
+        assert_eq!(lines[9], "accuracy 0.6000 of 10");
+    }
+}
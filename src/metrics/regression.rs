@@ -0,0 +1,277 @@
+use crate::metrics::{assert_paired, Metric};
+
+/// Scores of a single output dimension of a regressor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RegressionScores {
+    pub mae: f32,
+    pub mse: f32,
+    pub rmse: f32,
+    pub r2: f32,
+    pub explained_variance: f32,
+    /// Mean absolute percentage error as a fraction, so 0.1 means 10%.
+    /// Targets of zero are divided by `f32::EPSILON` instead.
+    pub mape: f32,
+}
+
+impl RegressionScores {
+    /// R² corrected for a model with `features` inputs fitted on `samples`
+    /// samples. Undefined, and returned as NaN, unless `samples > features + 1`.
+    pub fn adjusted_r2(&self, samples: usize, features: usize) -> f32 {
+        adjusted_r2(self.r2, samples, features)
+    }
+}
+
+pub fn adjusted_r2(r2: f32, samples: usize, features: usize) -> f32 {
+    if samples <= features + 1 {
+        return f32::NAN;
+    }
+    let n = samples as f32;
+    1. - (1. - r2) * (n - 1.) / (n - features as f32 - 1.)
+}
+
+fn mean(values: &[f32]) -> f32 {
+    values.iter().sum::<f32>() / values.len().max(1) as f32
+}
+
+fn variance(values: &[f32]) -> f32 {
+    let mean = mean(values);
+    mean_of(values.iter().map(|v| (v - mean).powi(2)), values.len())
+}
+
+fn mean_of(values: impl Iterator<Item = f32>, count: usize) -> f32 {
+    values.sum::<f32>() / count.max(1) as f32
+}
+
+// 1 - residual / total, where a constant target is explained perfectly or
+// not at all.
+fn explained(residual: f32, total: f32) -> f32 {
+    match (residual == 0., total == 0.) {
+        (true, _) => 1.,
+        (false, true) => 0.,
+        (false, false) => 1. - residual / total,
+    }
+}
+
+fn scores(predicted: &[f32], actual: &[f32]) -> RegressionScores {
+    let count = actual.len();
+    let errors: Vec<f32> = actual.iter().zip(predicted).map(|(a, p)| a - p).collect();
+    let mse = mean_of(errors.iter().map(|e| e * e), count);
+    RegressionScores {
+        mae: mean_of(errors.iter().map(|e| e.abs()), count),
+        mse,
+        rmse: mse.sqrt(),
+        r2: explained(mse, variance(actual)),
+        explained_variance: explained(variance(&errors), variance(actual)),
+        mape: mean_of(
+            errors
+                .iter()
+                .zip(actual)
+                .map(|(e, a)| (e / a.abs().max(f32::EPSILON)).abs()),
+            count,
+        ),
+    }
+}
+
+/// Scores for every output dimension of `predictions` against `targets`.
+/// Panics unless there is one target per prediction and every row is as
+/// wide as the first target.
+pub fn regression_scores(predictions: &[Vec<f32>], targets: &[Vec<f32>]) -> Vec<RegressionScores> {
+    assert_paired(predictions, targets);
+    let outputs = targets.first().map_or(0, |target| target.len());
+    for (prediction, target) in predictions.iter().zip(targets) {
+        assert!(
+            prediction.len() == outputs && target.len() == outputs,
+            "rows of {} predicted and {} target values, expected {}",
+            prediction.len(),
+            target.len(),
+            outputs
+        );
+    }
+    (0..outputs)
+        .map(|i| {
+            let predicted: Vec<f32> = predictions.iter().map(|p| p[i]).collect();
+            let actual: Vec<f32> = targets.iter().map(|t| t[i]).collect();
+            scores(&predicted, &actual)
+        })
+        .collect()
+}
+
+// The metrics below average one field of `RegressionScores` over the outputs.
+fn uniform(
+    predictions: &[Vec<f32>],
+    targets: &[Vec<f32>],
+    field: impl Fn(&RegressionScores) -> f32,
+) -> f32 {
+    let scores = regression_scores(predictions, targets);
+    mean(&scores.iter().map(field).collect::<Vec<f32>>())
+}
+
+macro_rules! regression_metric {
+    ($(#[$doc:meta])* $metric:ident, $name:expr, $field:ident) => {
+        $(#[$doc])*
+        #[derive(Default)]
+        pub struct $metric {}
+
+        impl $metric {
+            pub fn new() -> Self {
+                Self {}
+            }
+        }
+
+        impl Metric for $metric {
+            fn name(&self) -> &str {
+                $name
+            }
+
+            fn compute(&self, predictions: &[Vec<f32>], targets: &[Vec<f32>]) -> f32 {
+                uniform(predictions, targets, |scores| scores.$field)
+            }
+        }
+    };
+}
+
+regression_metric!(
+    /// Mean absolute error, averaged over outputs.
+    Mae,
+    "mae",
+    mae
+);
+regression_metric!(
+    /// Root mean squared error, averaged over outputs.
+    Rmse,
+    "rmse",
+    rmse
+);
+regression_metric!(
+    /// R², averaged over outputs.
+    R2,
+    "r2",
+    r2
+);
+regression_metric!(
+    /// Explained variance, averaged over outputs.
+    ExplainedVariance,
+    "explained_variance",
+    explained_variance
+);
+regression_metric!(
+    /// Mean absolute percentage error as a fraction, averaged over outputs.
+    Mape,
+    "mape",
+    mape
+);
+
+/// R² adjusted for the number of input features, averaged over outputs.
+pub struct AdjustedR2 {
+    features: usize,
+}
+
+impl AdjustedR2 {
+    pub fn new(features: usize) -> Self {
+        Self { features }
+    }
+}
+
+impl Metric for AdjustedR2 {
+    fn name(&self) -> &str {
+        "adjusted_r2"
+    }
+
+    fn compute(&self, predictions: &[Vec<f32>], targets: &[Vec<f32>]) -> f32 {
+        let r2 = uniform(predictions, targets, |scores| scores.r2);
+        adjusted_r2(r2, targets.len(), self.features)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::close;
+
+    #[test]
+    fn single_output() {
+        let targets = vec![vec![3.], vec![-0.5], vec![2.], vec![7.]];
+        let predictions = vec![vec![2.5], vec![0.], vec![2.], vec![8.]];
+        let scores = regression_scores(&predictions, &targets)[0];
+        assert!(close(scores.mae, 0.5));
+        assert!(close(scores.mse, 0.375));
+        assert!(close(scores.rmse, 0.375_f32.sqrt()));
+        assert!(close(scores.r2, 0.948_608_1));
+        assert!(close(scores.explained_variance, 0.957_173_3));
+        assert!(close(scores.mape, (0.5 / 3. + 1. + 0. + 1. / 7.) / 4.));
+        assert!(close(
+            scores.adjusted_r2(4, 1),
+            1. - (1. - 0.948_608_1) * 3. / 2.
+        ));
+        assert!(scores.adjusted_r2(3, 2).is_nan());
+    }
+
+    #[test]
+    fn per_output() {
+        let targets = vec![vec![0.5, 1.], vec![-1., 1.], vec![7., -6.]];
+        let predictions = vec![vec![0., 2.], vec![-1., 2.], vec![8., -5.]];
+        let scores = regression_scores(&predictions, &targets);
+        assert_eq!(scores.len(), 2);
+        assert!(close(scores[0].mae, 0.5));
+        assert!(close(scores[1].mae, 1.));
+        assert!(close(Mae::new().compute(&predictions, &targets), 0.75));
+        assert!(close(scores[0].r2, 0.965_437_8));
+        assert!(close(scores[1].r2, 0.908_163_3));
+        assert!(close(
+            R2::new().compute(&predictions, &targets),
+            0.936_800_5
+        ));
+        assert!(close(
+            Rmse::new().compute(&predictions, &targets),
+            ((1.25_f32 / 3.).sqrt() + 1.) / 2.
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "rows of 1 predicted and 2 target values, expected 2")]
+    fn mismatched_widths() {
+        regression_scores(&[vec![1., 2.], vec![3.]], &[vec![1., 2.], vec![3., 4.]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "3 predictions but 2 targets")]
+    fn mismatched_counts() {
+        Mae::new().compute(&[vec![1.], vec![2.], vec![3.]], &[vec![1.], vec![2.]]);
+    }
+
+    #[test]
+    fn constant_targets() {
+        let targets = vec![vec![1.]; 3];
+        let scores = regression_scores(&targets, &targets)[0];
+        assert_eq!(scores.r2, 1.);
+        assert_eq!(scores.explained_variance, 1.);
+        let predictions = vec![vec![2.]; 3];
+        assert_eq!(regression_scores(&predictions, &targets)[0].r2, 0.);
+    }
+
+    #[test]
+    fn fit_reports_regression_metrics() {
+        use crate::activations::Activation::*;
+        use crate::data::Dataset;
+        use crate::rann::Rann;
+        use crate::training::FitConfig;
+
+        let inputs: Vec<Vec<f32>> = (0..64).map(|i| vec![i as f32 / 64.]).collect();
+        let targets: Vec<Vec<f32>> = inputs.iter().map(|x| vec![2. * x[0] - 0.5]).collect();
+        let data = (inputs, targets);
+        let mut rann = Rann::with_activations(&[(1, Linear), (1, Linear)]).with_seed(0);
+        let history = rann.fit(
+            &data,
+            FitConfig {
+                epochs: 200,
+                batch_size: 8,
+                metrics: vec![Box::new(R2::new()), Box::new(Mae::new())],
+                ..FitConfig::default()
+            },
+        );
+        assert!(history.metric("r2")[199] > 0.99);
+
+        let scores = regression_scores(&rann.predict(&data), &data.targets());
+        assert!(scores[0].mae < 0.05);
+    }
+}
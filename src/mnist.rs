@@ -1,76 +1,41 @@
-use crate::activations::{Sigmoid, RELU};
-use crate::math::one_hot_encode;
-use crate::node::{Layer, LayerDense, RannV2};
-use crate::rann::Rann;
-use std::cmp::Ordering;
-use std::env;
-use std::error::Error;
-use std::ffi::OsString;
-use std::fs::File;
-use std::process;
-use std::rc::Rc;
+use crate::data::mnist::MnistLoader;
+use crate::data::DataError;
 
-fn run() -> Result<(Vec<Vec<f32>>, Vec<Vec<f32>>), Box<dyn Error>> {
-    // let file_path = get_first_arg()?;
-    let file_path = "../../Downloads/mnist_train.csv";
-    println!("Path: {:?}", file_path);
-    let file = File::open(file_path)?;
-    let mut rdr = csv::Reader::from_reader(file);
+type Samples = Vec<Vec<f32>>;
 
-    let mut training_set: Vec<Vec<f32>> = Vec::new();
-    let mut labels: Vec<Vec<f32>> = Vec::new();
+const MNIST_CSV: &str = "../../Downloads/mnist_train.csv";
 
-    let max = 6000;
-    let mut counter = 0;
-    for result in rdr.records() {
-        if counter > max {
-            break;
-        }
-        let mut record = result?;
-        //        let val = record.(",").collect();
-        let label = record.get(0).unwrap().parse::<usize>().unwrap();
-        let mut training = vec![0.0_f32; 28 * 28];
-        for i in 0..28 * 28 {
-            training[i] = record.get(i + 1).unwrap().parse::<f32>().unwrap() / 255.;
-        }
-        labels.push(
-            one_hot_encode(label, 10)
-                .into_iter()
-                .map(|val| val as f32)
-                .collect(),
-        );
-        //labels.push(vec![label]);
-        training_set.push(training);
-        counter += 1;
-    }
-
-    Ok((training_set, labels))
-}
-
-/// Returns the first positional argument sent to this process. If there are no
-/// positional arguments, then this returns an error.
-fn get_first_arg() -> Result<OsString, Box<dyn Error>> {
-    match env::args_os().nth(1) {
-        None => Err(From::from("expected 1 argument, but got none")),
-        Some(file_path) => Ok(file_path),
-    }
+fn run() -> Result<(Samples, Samples), DataError> {
+    println!("Path: {:?}", MNIST_CSV);
+    Ok(MnistLoader::new()
+        .limit(6000)
+        .load_csv(MNIST_CSV)?
+        .into_parts())
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::data::split::stratified_split;
+    use crate::data::Dataset;
+    use crate::metrics::{Accuracy, ConfusionMatrix, Metric};
+    use crate::node::RannV2;
+    use crate::rann::Rann;
+
+    // predictions for `data` next to the labels they are scored against
+    fn validate<D: Dataset>(rannv2: &mut RannV2, data: &D) -> (Samples, Samples) {
+        (rannv2.predict(data), data.targets())
+    }
 
     #[test]
+    #[ignore = "needs the MNIST csv at ../../Downloads/mnist_train.csv"]
     fn woll() {
         println!("Start");
         match run() {
-            Err(err) => {
-                println!("{}", err);
-                process::exit(1);
-            }
+            Err(err) => panic!("{}", err),
             Ok((training_set, labels)) => {
                 println!("Labes: {:?}", labels);
-                let mut rann = Rann::new(&vec![28 * 28, 100, 1]);
+                let mut rann = Rann::new(&[28 * 28, 100, 1]);
                 for _ in 0..100 {
                     rann.train(&training_set, &labels);
                 }
@@ -84,68 +49,33 @@ mod test {
     }
 
     #[test]
+    #[ignore = "needs the MNIST csv at ../../Downloads/mnist_train.csv"]
     fn mnist_layer() {
         let mut rannv2 = RannV2::new(vec![28 * 28, 32, 10]);
-        let train_count = 5000;
         println!("Start");
         match run() {
-            Err(err) => {
-                println!("{}", err);
-                process::exit(1);
-            }
-            Ok((training_set, labels)) => {
+            Err(err) => panic!("{}", err),
+            Ok(data) => {
+                let parts = stratified_split(&data, &[5., 1.], 0);
+                let (train, validation) = (&parts[0], &parts[1]);
                 for i in 0..20 {
                     println!("Iteration: {}", i);
-                    for (train_set, target) in
-                        training_set[..train_count].iter().zip(labels.clone())
-                    {
-                        let out = rannv2.forward(&train_set);
-                        //println!("---------------------------");
-                        //println!("Pred: {:?}", out);
-                        //println!("Target: {:?}", target);
+                    for index in 0..train.len() {
+                        let (input, target) = train.get(index);
+                        rannv2.forward(&input);
                         rannv2.backwards(&target);
                     }
-                    println!("Validation");
-                    let mut correct_count = 0;
-                    for (train_set, target) in
-                        training_set[train_count..].iter().zip(labels.clone())
-                    {
-                        let out = rannv2.forward(&train_set);
-                        let mut pred = 100;
-                        let mut targ = 1001;
-                        for i in 0..out.len() {
-                            if out[i] > 0.8 {
-                                pred = i;
-                            }
-                            if target[i] > 0.9 {
-                                targ = i;
-                            }
-                        }
-                        if pred == targ {
-                            correct_count += 1;
-                        }
-                    }
-                    println!("Correct: {} of 1000", correct_count);
-                }
-                println!("Prediction for 10 first");
-                let mut correct_count = 0;
-                for (train_set, target) in training_set[train_count..].iter().zip(labels.clone()) {
-                    let out = rannv2.forward(&train_set);
-                    let mut pred = 100;
-                    let mut targ = 1001;
-                    for i in 0..out.len() {
-                        if out[i] > 0.8 {
-                            pred = i;
-                        }
-                        if target[i] > 0.9 {
-                            targ = i;
-                        }
-                    }
-                    if pred == targ {
-                        correct_count += 1;
-                    }
+                    let (predictions, targets) = validate(&mut rannv2, validation);
+                    println!(
+                        "Validation accuracy: {}",
+                        Accuracy::new().compute(&predictions, &targets)
+                    );
                 }
-                println!("Correct: {} of 500", correct_count);
+                let (predictions, targets) = validate(&mut rannv2, validation);
+                println!(
+                    "{}",
+                    ConfusionMatrix::from_predictions(&predictions, &targets)
+                );
             }
         }
     }
@@ -1,12 +1,25 @@
-use crate::activations::{ActivationFunc, Sigmoid, RELU};
-use crate::math::{softmax, softmax_derivative};
-use rand::Rng;
+use crate::activations::{ActivationFunc, Sigmoid};
+use crate::data::Dataset;
+use crate::initializers::Initializer;
+use crate::layers::{Layer, Parameter};
+use crate::losses::{CategoricalCrossEntropy, Loss};
+use crate::math::softmax;
+use crate::metrics::Metric;
+use crate::optimizers::{Optimizer, Sgd};
+use crate::rann::Rann;
+use crate::sequential::Sequential;
+use crate::serialization::{LayerRecord, ModelError, Registry};
+use crate::tensor::Tensor;
+use crate::training::{FitConfig, History, Scores};
+use rand::RngCore;
+use std::path::Path;
 use std::rc::Rc;
 
 pub struct Node {
     pub inputs: Vec<f32>,
     pub output: f32,
     pub weights: Vec<f32>,
+    pub bias: f32,
     pub activation: Rc<dyn ActivationFunc>,
     pub number_of_inputs: usize,
 }
@@ -22,6 +35,7 @@ impl Node {
                 Some(ws) => ws,
                 None => Self::gen_weights(number_of_inputs),
             },
+            bias: 0.,
             output: 0.,
             inputs: vec![],
             number_of_inputs,
@@ -31,44 +45,53 @@ impl Node {
 
     fn gen_weights(count: usize) -> Vec<f32> {
         let mut rng = rand::thread_rng();
-        vec![0; count].iter().map(|_| rng.gen::<f32>()).collect()
+        Initializer::Uniform { low: 0., high: 1. }
+            .initialize(&[count], count, 1, &mut rng)
+            .into_data()
     }
 
-    pub fn forward(&mut self, inputs: &Vec<f32>) -> f32 {
-        self.inputs = inputs.clone();
-        let output = self.weights.iter().zip(inputs).map(|(w, i)| w * i).sum();
-        let output = self.activation.compute(output);
+    pub fn forward(&mut self, inputs: &[f32]) -> f32 {
+        self.inputs = inputs.to_vec();
+        let output: f32 = self.weights.iter().zip(inputs).map(|(w, i)| w * i).sum();
+        let output = self.activation.compute(output + self.bias);
         self.output = output;
         output
     }
 
     pub fn compute_derivatives(&self, dc_da: f32) -> Vec<f32> {
         let part1_update = dc_da * self.activation.compute_derivative(self.output);
-        let mut weight_derivatives = vec![0.; self.number_of_inputs];
-        for i in 0..self.number_of_inputs {
-            weight_derivatives[i] = part1_update * self.inputs[i];
-        }
-        weight_derivatives
+        self.inputs
+            .iter()
+            .take(self.number_of_inputs)
+            .map(|input| part1_update * input)
+            .collect()
+    }
+
+    pub fn compute_bias_derivative(&self, dc_da: f32) -> f32 {
+        dc_da * self.activation.compute_derivative(self.output)
     }
 
     pub fn update_weights(&mut self, delta_weights: Vec<f32>) {
-        //println!("Weight update: {:?}", delta_weights);
-        // panic!();
-        for i in 0..delta_weights.len() {
-            self.weights[i] -= delta_weights[i];
+        for (weight, delta) in self.weights.iter_mut().zip(delta_weights) {
+            *weight -= delta;
         }
     }
 }
 
-pub trait Layer {
-    fn forward(&mut self, inputs: &Vec<f32>) -> Vec<f32>;
-    fn backwards(&mut self, inputs: &Vec<f32>) -> Vec<f32>;
-}
-
+/// A dense layer with the semantics of `Node`: every output is
+/// `activation(weights · inputs + bias)`. The weights of all nodes live in one
+/// row-major tensor, one row per node.
 pub struct LayerDense {
-    nodes: Vec<Node>,
-    input_nodes_count: usize,
-    nodes_count: usize,
+    weights: Tensor,
+    weight_gradients: Tensor,
+    biases: Tensor,
+    bias_gradients: Tensor,
+    activation: Rc<dyn ActivationFunc>,
+    inputs: Vec<f32>,
+    outputs: Vec<f32>,
+    weight_initializer: Initializer,
+    bias_initializer: Initializer,
+    use_bias: bool,
 }
 
 impl LayerDense {
@@ -77,51 +100,146 @@ impl LayerDense {
         nodes_count: usize,
         activation: Rc<dyn ActivationFunc>,
     ) -> Self {
-        Self {
-            input_nodes_count,
-            nodes_count,
-            nodes: vec![0; nodes_count]
-                .iter()
-                .map(|_| Node::new(input_nodes_count, activation.clone(), None))
-                .collect(),
+        let mut layer = Self {
+            weights: Tensor::zeros(&[nodes_count, input_nodes_count]),
+            weight_gradients: Tensor::zeros(&[nodes_count, input_nodes_count]),
+            biases: Tensor::zeros(&[nodes_count]),
+            bias_gradients: Tensor::zeros(&[nodes_count]),
+            activation,
+            inputs: vec![],
+            outputs: vec![],
+            weight_initializer: Initializer::Uniform { low: 0., high: 1. },
+            bias_initializer: Initializer::Zeros,
+            use_bias: true,
+        };
+        layer.initialize(&mut rand::thread_rng());
+        layer
+    }
+
+    pub fn with_initializer(mut self, initializer: Initializer) -> Self {
+        self.weight_initializer = initializer;
+        self.initialize(&mut rand::thread_rng());
+        self
+    }
+
+    pub fn with_bias_initializer(mut self, initializer: Initializer) -> Self {
+        if self.use_bias {
+            self.bias_initializer = initializer;
+            self.initialize(&mut rand::thread_rng());
         }
+        self
+    }
+
+    pub fn without_bias(mut self) -> Self {
+        self.biases.fill(0.);
+        self.bias_initializer = Initializer::Zeros;
+        self.use_bias = false;
+        self
+    }
+
+    /// The weights, one row per node.
+    pub fn weights(&self) -> &Tensor {
+        &self.weights
+    }
+
+    /// Rebuilds a layer saved with `to_record`.
+    pub fn from_record(record: &LayerRecord, registry: &Registry) -> Result<Self, ModelError> {
+        let shape = [record.outputs, record.inputs];
+        let weights = record.expect_tensor("weights", &shape)?.clone();
+        let biases = match record.tensor("biases") {
+            Some(_) => Some(record.expect_tensor("biases", &[record.outputs])?.clone()),
+            None => None,
+        };
+        Ok(Self {
+            weights,
+            weight_gradients: Tensor::zeros(&shape),
+            use_bias: biases.is_some(),
+            biases: biases.unwrap_or_else(|| Tensor::zeros(&[record.outputs])),
+            bias_gradients: Tensor::zeros(&[record.outputs]),
+            activation: registry.record_activation(record)?.into(),
+            inputs: vec![],
+            outputs: vec![],
+            weight_initializer: Initializer::Uniform { low: 0., high: 1. },
+            bias_initializer: Initializer::Zeros,
+        })
     }
 }
 
 impl Layer for LayerDense {
-    fn forward(&mut self, inputs: &Vec<f32>) -> Vec<f32> {
-        self.nodes
-            .iter_mut()
-            .map(|node| node.forward(inputs))
-            .collect()
+    fn forward(&mut self, inputs: &[f32]) -> Vec<f32> {
+        self.inputs = inputs.to_vec();
+        self.outputs = self
+            .weights
+            .matmul(&Tensor::vector(inputs))
+            .data()
+            .iter()
+            .zip(self.biases.data())
+            .map(|(sum, bias)| self.activation.compute(sum + bias))
+            .collect();
+        self.outputs.clone()
+    }
+
+    fn backwards(&mut self, derivatives: &[f32]) -> Vec<f32> {
+        let deltas: Vec<f32> = self
+            .outputs
+            .iter()
+            .zip(derivatives)
+            .map(|(output, derivative)| self.activation.compute_derivative(*output) * derivative)
+            .collect();
+        self.weight_gradients
+            .add_assign(&Tensor::outer(&deltas, &self.inputs));
+        if self.use_bias {
+            self.bias_gradients.add_assign(&Tensor::vector(&deltas));
+        }
+        Tensor::vector(&deltas).matmul(&self.weights).into_data()
+    }
+
+    fn outputs(&self) -> usize {
+        self.biases.len()
     }
 
-    fn backwards(&mut self, derivatives: &Vec<f32>) -> Vec<f32> {
-        let learning_rate = 0.01;
-        let mut derivatives_for_input_nodes = vec![0.; self.input_nodes_count];
-        for i in 0..self.input_nodes_count {
-            derivatives_for_input_nodes[i] = self
-                .nodes
-                .iter()
-                .enumerate()
-                .map(|(j, node)| {
-                    node.activation.compute_derivative(node.output)
-                        * node.weights[i]
-                        * derivatives[j]
-                })
-                .sum();
+    fn initialize(&mut self, rng: &mut dyn RngCore) {
+        let (nodes, inputs) = (self.weights.shape()[0], self.weights.shape()[1]);
+        self.weights = self
+            .weight_initializer
+            .initialize(&[nodes, inputs], inputs, nodes, rng);
+        if self.use_bias {
+            self.biases = self
+                .bias_initializer
+                .initialize(&[nodes], inputs, nodes, rng);
         }
+    }
 
-        for i in 0..self.nodes_count {
-            let node_ders = self.nodes[i]
-                .compute_derivatives(derivatives[i])
-                .iter()
-                .map(|val| val * learning_rate)
-                .collect();
-            self.nodes[i].update_weights(node_ders);
+    fn parameters(&mut self) -> Vec<Parameter<'_>> {
+        let mut parameters = vec![Parameter {
+            values: self.weights.data_mut(),
+            gradients: self.weight_gradients.data_mut(),
+        }];
+        if self.use_bias {
+            parameters.push(Parameter {
+                values: self.biases.data_mut(),
+                gradients: self.bias_gradients.data_mut(),
+            });
         }
+        parameters
+    }
 
-        derivatives_for_input_nodes
+    fn to_record(&self) -> Result<LayerRecord, ModelError> {
+        let mut record = LayerRecord::new(
+            "node_dense",
+            self.weights.shape()[1],
+            self.weights.shape()[0],
+        );
+        record.activation = Some(self.activation.name().to_string());
+        record
+            .tensors
+            .push(("weights".to_string(), self.weights.clone()));
+        if self.use_bias {
+            record
+                .tensors
+                .push(("biases".to_string(), self.biases.clone()));
+        }
+        Ok(record)
     }
 }
 
@@ -142,96 +260,169 @@ impl LayerSoftmax {
 }
 
 impl Layer for LayerSoftmax {
-    fn forward(&mut self, inputs: &Vec<f32>) -> Vec<f32> {
-        self.inputs = inputs.clone();
+    fn forward(&mut self, inputs: &[f32]) -> Vec<f32> {
+        debug_assert_eq!(inputs.len(), self.input_nodes_count);
+        self.inputs = inputs.to_vec();
         self.outputs = softmax(inputs);
         self.outputs.clone()
     }
 
-    fn backwards(&mut self, derivatives: &Vec<f32>) -> Vec<f32> {
-        softmax_derivative(&self.inputs)
+    fn backwards(&mut self, derivatives: &[f32]) -> Vec<f32> {
+        // Jacobian-vector product: ds_i/dx_j = s_i * (1[i == j] - s_j)
+        let weighted: f32 = self
+            .outputs
             .iter()
             .zip(derivatives)
-            .map(|(sd, d)| {
-                if sd.is_nan() {
-                    return 0.1 * d;
-                }
-                sd * d
-            })
+            .map(|(s, d)| s * d)
+            .sum();
+        self.outputs
+            .iter()
+            .zip(derivatives)
+            .map(|(s, d)| s * (d - weighted))
             .collect()
     }
+
+    fn outputs(&self) -> usize {
+        self.input_nodes_count
+    }
+
+    fn to_record(&self) -> Result<LayerRecord, ModelError> {
+        Ok(LayerRecord::new(
+            "softmax",
+            self.input_nodes_count,
+            self.input_nodes_count,
+        ))
+    }
 }
 
 pub struct RannV2 {
-    layers: Vec<LayerDense>,
-    output_layer: LayerSoftmax,
+    model: Rann,
 }
 
 impl RannV2 {
     pub fn new(layer_sizes: Vec<usize>) -> Self {
         assert!(layer_sizes.len() > 2);
 
-        let mut layers = vec![];
-        for i in 1..layer_sizes.len() {
-            layers.push(LayerDense::new(
-                layer_sizes[i - 1],
-                layer_sizes[i],
-                Rc::new(Sigmoid::new()),
-            ));
+        let mut network = Sequential::new();
+        for sizes in layer_sizes.windows(2) {
+            network = network.add(LayerDense::new(sizes[0], sizes[1], Rc::new(Sigmoid::new())));
         }
+        network = network.add(LayerSoftmax::new(layer_sizes[layer_sizes.len() - 1]));
 
         Self {
-            layers,
-            output_layer: LayerSoftmax::new(layer_sizes[layer_sizes.len() - 1]),
+            model: Rann::from(network)
+                .with_loss(CategoricalCrossEntropy::new())
+                .with_optimizer(Sgd::new(0.01)),
         }
     }
 
-    pub fn forward(&mut self, inputs: &Vec<f32>) -> Vec<f32> {
-        let mut output = inputs.clone();
-        for layer in self.layers.iter_mut() {
-            output = layer.forward(&output);
+    pub fn with_loss<L: Loss + 'static>(self, loss: L) -> Self {
+        Self {
+            model: self.model.with_loss(loss),
         }
-        self.output_layer.forward(&output)
     }
 
-    pub fn backwards(&mut self, target: &Vec<f32>) {
-        let error_grad: Vec<f32> = target
-            .iter()
-            .zip(self.output_layer.outputs.clone())
-            .map(|(t, pred)| pred - t)
-            .collect();
-        let debug = false;
-        if debug {
-            println!("Error: {:?}", error_grad);
-        }
-        let mut error_grad = self.output_layer.backwards(&error_grad);
-        if debug {
-            println!("Error: {:?}", error_grad);
+    pub fn with_optimizer<O: Optimizer + 'static>(self, optimizer: O) -> Self {
+        Self {
+            model: self.model.with_optimizer(optimizer),
         }
-        for layer in self.layers.iter_mut().rev() {
-            error_grad = layer.backwards(&error_grad);
-            if debug {
-                println!("Error: {:?}", error_grad);
-            }
+    }
+
+    /// Re-draws every weight from `seed`, overwriting any set before, see
+    /// `Rann::with_seed`.
+    pub fn with_seed(self, seed: u64) -> Self {
+        Self {
+            model: self.model.with_seed(seed),
         }
     }
+
+    pub fn forward(&mut self, inputs: &[f32]) -> Vec<f32> {
+        self.model.forward(inputs)
+    }
+
+    pub fn backwards(&mut self, target: &[f32]) -> f32 {
+        self.model.backwards(target)
+    }
+
+    pub fn train_batch(&mut self, inputs: &[Vec<f32>], targets: &[Vec<f32>]) -> f32 {
+        self.model.train_batch(inputs, targets)
+    }
+
+    pub fn network_mut(&mut self) -> &mut Sequential {
+        self.model.network_mut()
+    }
+
+    pub fn fit<D: Dataset + ?Sized>(&mut self, dataset: &D, config: FitConfig) -> History {
+        self.model.fit(dataset, config)
+    }
+
+    pub fn predict<D: Dataset + ?Sized>(&mut self, dataset: &D) -> Vec<Vec<f32>> {
+        self.model.predict(dataset)
+    }
+
+    pub fn evaluate<D: Dataset + ?Sized>(
+        &mut self,
+        dataset: &D,
+        metrics: &[Box<dyn Metric>],
+    ) -> Scores {
+        self.model.evaluate(dataset, metrics)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ModelError> {
+        self.model.save(path)
+    }
+
+    /// Loads a model written by `save` with the loss and optimizer of `new`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ModelError> {
+        Ok(Self {
+            model: Rann::load(path)?
+                .with_loss(CategoricalCrossEntropy::new())
+                .with_optimizer(Sgd::new(0.01)),
+        })
+    }
+
+    pub fn export_onnx<P: AsRef<Path>>(&self, path: P) -> Result<(), ModelError> {
+        self.model.export_onnx(path)
+    }
+
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> Result<String, ModelError> {
+        self.model.to_json()
+    }
+
+    #[cfg(feature = "json")]
+    pub fn from_json(json: &str) -> Result<Self, ModelError> {
+        Ok(Self {
+            model: Rann::from_json(json)?
+                .with_loss(CategoricalCrossEntropy::new())
+                .with_optimizer(Sgd::new(0.01)),
+        })
+    }
+}
+
+impl From<RannV2> for Rann {
+    fn from(rannv2: RannV2) -> Self {
+        rannv2.model
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::activations::Relu;
+    use crate::testing::temp;
 
     #[test]
     fn node_forward() {
-        let mut node = Node::new(3, Rc::new(RELU::new()), Some(vec![1., 2., 3.]));
-        let output = node.forward(&vec![1., 2., 3.]);
+        let mut node = Node::new(3, Rc::new(Relu::new()), Some(vec![1., 2., 3.]));
+        let output = node.forward(&[1., 2., 3.]);
         assert_eq!(output, 14.);
     }
 
     #[test]
     fn node_derivatives_perfect_weights() {
-        let mut node = Node::new(3, Rc::new(RELU::new()), Some(vec![1., 2., 3.]));
-        let output = node.forward(&vec![1., 2., 3.]);
+        let mut node = Node::new(3, Rc::new(Relu::new()), Some(vec![1., 2., 3.]));
+        let _output = node.forward(&[1., 2., 3.]);
         let dervs = node.compute_derivatives(0.);
         assert_eq!(dervs, vec![0., 0., 0.]);
     }
@@ -239,9 +430,12 @@ mod test {
     #[test]
     fn node_derivatives() {
         let mut node = Node::new(3, Rc::new(Sigmoid::new()), Some(vec![0.1, 0.3, 0.5]));
-        let output = node.forward(&vec![1., 4., 5.]);
+        let _output = node.forward(&[1., 4., 5.]);
         let dervs = node.compute_derivatives(0.1502);
-        assert_eq!(dervs, vec![0.002, 0.0079, 0.0099]);
+        let expected = [0.003215, 0.012859, 0.016073];
+        for (d, e) in dervs.iter().zip(expected.iter()) {
+            assert!((d - e).abs() < 1e-5);
+        }
     }
 
     #[test]
@@ -254,7 +448,7 @@ mod test {
         for _ in 0..100000 {
             let output1 = node1.forward(&input);
             let output2 = node2.forward(&input);
-            let output3 = node3.forward(&vec![output1, output2]);
+            let output3 = node3.forward(&[output1, output2]);
             let error_delta = output3 - target;
             let node3de = node3.compute_derivatives(error_delta);
             let common = node3.activation.compute_derivative(node3.output) * error_delta;
@@ -270,7 +464,7 @@ mod test {
 
         let output1 = node1.forward(&input);
         let output2 = node2.forward(&input);
-        let output3 = node3.forward(&vec![output1, output2]);
+        let output3 = node3.forward(&[output1, output2]);
         let error_delta = output3 - target;
         assert!(error_delta < 0.002);
     }
@@ -281,6 +475,7 @@ mod test {
         let mut layer2 = LayerDense::new(3, 1, Rc::new(Sigmoid::new()));
         let input = vec![1.1, 1.2, 1.3];
         let target = 0.25;
+        let mut optimizer = Sgd::new(0.01);
         for _ in 0..100000 {
             let outp1 = layer1.forward(&input);
             let outp2 = layer2.forward(&outp1);
@@ -289,6 +484,11 @@ mod test {
             let grads1 = vec![error];
             let grads2 = layer2.backwards(&grads1);
             layer1.backwards(&grads2);
+            let mut parameters = layer1.parameters();
+            parameters.extend(layer2.parameters());
+            optimizer.step(&mut parameters);
+            layer1.zero_gradients();
+            layer2.zero_gradients();
         }
         let outp1 = layer1.forward(&input);
         let outp2 = layer2.forward(&outp1);
@@ -296,11 +496,64 @@ mod test {
         assert!(error_delta < 0.002);
     }
 
+    #[test]
+    fn node_bias() {
+        let mut node = Node::new(2, Rc::new(Relu::new()), Some(vec![1., 1.]));
+        node.bias = 0.5;
+        assert_eq!(node.forward(&[1., 2.]), 3.5);
+        assert_eq!(node.compute_bias_derivative(2.), 2.);
+    }
+
+    #[test]
+    fn node_layer_bias() {
+        let mut layer = LayerDense::new(2, 2, Rc::new(Relu::new()));
+        layer.forward(&[1., 1.]);
+        layer.backwards(&[1., 1.]);
+        let parameters = layer.parameters();
+        assert_eq!(parameters.len(), 2);
+        assert_eq!(parameters[1].gradients, &[1., 1.]);
+
+        let mut layer = LayerDense::new(2, 2, Rc::new(Relu::new())).without_bias();
+        layer.forward(&[1., 1.]);
+        layer.backwards(&[1., 1.]);
+        assert_eq!(layer.parameters().len(), 1);
+    }
+
+    #[test]
+    fn node_layer_initializers() {
+        let mut layer = LayerDense::new(2, 3, Rc::new(Relu::new()))
+            .with_initializer(Initializer::Constant(2.))
+            .with_bias_initializer(Initializer::Constant(1.));
+        assert_eq!(layer.forward(&[1., 1.]), vec![5., 5., 5.]);
+
+        let layer = LayerDense::new(4, 4, Rc::new(Relu::new()))
+            .with_initializer(Initializer::Orthogonal { gain: 1. });
+        for row in layer.weights().rows() {
+            let norm: f32 = row.iter().map(|w| w * w).sum();
+            assert!((norm - 1.).abs() < 1e-4);
+        }
+    }
+
     #[test]
     fn rannv2_test() {
         let mut rannv2 = RannV2::new(vec![3, 3, 8]);
-        let result = rannv2.forward(&vec![3., 4., 7.]);
-        assert!(result.into_iter().sum::<f32>() == 1.);
+        let result = rannv2.forward(&[3., 4., 7.]);
+        assert!((result.into_iter().sum::<f32>() - 1.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rannv2_save_load() {
+        let path = temp("rannv2_save_load.rann");
+        let mut rannv2 = RannV2::new(vec![3, 4, 2]);
+        rannv2.forward(&[3., 4., 7.]);
+        rannv2.backwards(&[0., 1.]);
+        rannv2.save(&path).unwrap();
+        let mut loaded = RannV2::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            loaded.forward(&[1., -2., 0.5]),
+            rannv2.forward(&[1., -2., 0.5])
+        );
     }
 
     #[test]
@@ -312,11 +565,12 @@ mod test {
                 0.11, -0.5, -0.8, 0.17, 0.3, 0.4, 0.5, 0.1, 0.6, 0.7, 0.11, -0.5, -0.8, 0.17,
             ]);
             println!("Pred: {:?}", result);
-            rannv2.backwards(&vec![0., 1., 0., 0., 0., 0., 0., 0., 0., 0.]);
+            rannv2.backwards(&[0., 1., 0., 0., 0., 0., 0., 0., 0., 0.]);
         }
     }
 
     #[test]
+    #[ignore = "long running"]
     fn rannv2_3test() {
         let mut rannv2 = RannV2::new(vec![3, 2, 2]);
         let train_set_1 = vec![vec![vec![1., 1., 1.], vec![1., 0.]]; 5000];
@@ -0,0 +1,349 @@
+use crate::layers::Parameter;
+
+pub trait Optimizer {
+    /// Applies one update to every parameter from its gradients. Parameters
+    /// are identified by their position, so they must be passed in the same
+    /// order on every step.
+    fn step(&mut self, parameters: &mut [Parameter]);
+
+    fn learning_rate(&self) -> f32;
+    /// Used by learning rate schedulers between steps.
+    fn set_learning_rate(&mut self, learning_rate: f32);
+}
+
+// Returns the state slot for parameter `index`, creating it on first use.
+fn state(states: &mut Vec<Vec<f32>>, index: usize, size: usize) -> &mut Vec<f32> {
+    if states.len() <= index {
+        states.resize(index + 1, vec![]);
+    }
+    if states[index].len() != size {
+        states[index] = vec![0.; size];
+    }
+    &mut states[index]
+}
+
+pub struct Sgd {
+    learning_rate: f32,
+    momentum: f32,
+    nesterov: bool,
+    velocities: Vec<Vec<f32>>,
+}
+
+impl Sgd {
+    pub fn new(learning_rate: f32) -> Self {
+        Self {
+            learning_rate,
+            momentum: 0.,
+            nesterov: false,
+            velocities: vec![],
+        }
+    }
+
+    pub fn with_momentum(mut self, momentum: f32) -> Self {
+        self.momentum = momentum;
+        self
+    }
+
+    pub fn with_nesterov(mut self, momentum: f32) -> Self {
+        self.momentum = momentum;
+        self.nesterov = true;
+        self
+    }
+}
+
+impl Optimizer for Sgd {
+    fn step(&mut self, parameters: &mut [Parameter]) {
+        for (index, parameter) in parameters.iter_mut().enumerate() {
+            if self.momentum == 0. {
+                for (value, gradient) in parameter.values.iter_mut().zip(parameter.gradients.iter())
+                {
+                    *value -= self.learning_rate * gradient;
+                }
+                continue;
+            }
+            let velocity = state(&mut self.velocities, index, parameter.values.len());
+            for ((value, gradient), v) in parameter
+                .values
+                .iter_mut()
+                .zip(parameter.gradients.iter())
+                .zip(velocity.iter_mut())
+            {
+                *v = self.momentum * *v + gradient;
+                let update = if self.nesterov {
+                    gradient + self.momentum * *v
+                } else {
+                    *v
+                };
+                *value -= self.learning_rate * update;
+            }
+        }
+    }
+
+    fn learning_rate(&self) -> f32 {
+        self.learning_rate
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f32) {
+        self.learning_rate = learning_rate;
+    }
+}
+
+pub struct RmsProp {
+    learning_rate: f32,
+    rho: f32,
+    epsilon: f32,
+    averages: Vec<Vec<f32>>,
+}
+
+impl RmsProp {
+    pub fn new(learning_rate: f32) -> Self {
+        Self {
+            learning_rate,
+            rho: 0.9,
+            epsilon: 1e-7,
+            averages: vec![],
+        }
+    }
+
+    pub fn with_rho(mut self, rho: f32) -> Self {
+        self.rho = rho;
+        self
+    }
+}
+
+impl Optimizer for RmsProp {
+    fn step(&mut self, parameters: &mut [Parameter]) {
+        for (index, parameter) in parameters.iter_mut().enumerate() {
+            let average = state(&mut self.averages, index, parameter.values.len());
+            for ((value, gradient), avg) in parameter
+                .values
+                .iter_mut()
+                .zip(parameter.gradients.iter())
+                .zip(average.iter_mut())
+            {
+                *avg = self.rho * *avg + (1. - self.rho) * gradient.powi(2);
+                *value -= self.learning_rate * gradient / (avg.sqrt() + self.epsilon);
+            }
+        }
+    }
+
+    fn learning_rate(&self) -> f32 {
+        self.learning_rate
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f32) {
+        self.learning_rate = learning_rate;
+    }
+}
+
+pub struct Adagrad {
+    learning_rate: f32,
+    epsilon: f32,
+    sums: Vec<Vec<f32>>,
+}
+
+impl Adagrad {
+    pub fn new(learning_rate: f32) -> Self {
+        Self {
+            learning_rate,
+            epsilon: 1e-7,
+            sums: vec![],
+        }
+    }
+}
+
+impl Optimizer for Adagrad {
+    fn step(&mut self, parameters: &mut [Parameter]) {
+        for (index, parameter) in parameters.iter_mut().enumerate() {
+            let sum = state(&mut self.sums, index, parameter.values.len());
+            for ((value, gradient), s) in parameter
+                .values
+                .iter_mut()
+                .zip(parameter.gradients.iter())
+                .zip(sum.iter_mut())
+            {
+                *s += gradient.powi(2);
+                *value -= self.learning_rate * gradient / (s.sqrt() + self.epsilon);
+            }
+        }
+    }
+
+    fn learning_rate(&self) -> f32 {
+        self.learning_rate
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f32) {
+        self.learning_rate = learning_rate;
+    }
+}
+
+pub struct Adam {
+    learning_rate: f32,
+    beta1: f32,
+    beta2: f32,
+    epsilon: f32,
+    weight_decay: f32,
+    steps: i32,
+    first_moments: Vec<Vec<f32>>,
+    second_moments: Vec<Vec<f32>>,
+}
+
+impl Adam {
+    pub fn new(learning_rate: f32) -> Self {
+        Self {
+            learning_rate,
+            beta1: 0.9,
+            beta2: 0.999,
+            epsilon: 1e-8,
+            weight_decay: 0.,
+            steps: 0,
+            first_moments: vec![],
+            second_moments: vec![],
+        }
+    }
+
+    pub fn with_betas(mut self, beta1: f32, beta2: f32) -> Self {
+        self.beta1 = beta1;
+        self.beta2 = beta2;
+        self
+    }
+}
+
+impl Optimizer for Adam {
+    fn step(&mut self, parameters: &mut [Parameter]) {
+        self.steps += 1;
+        let correction1 = 1. - self.beta1.powi(self.steps);
+        let correction2 = 1. - self.beta2.powi(self.steps);
+        for (index, parameter) in parameters.iter_mut().enumerate() {
+            let size = parameter.values.len();
+            let first = state(&mut self.first_moments, index, size);
+            let second = state(&mut self.second_moments, index, size);
+            for (i, (value, gradient)) in parameter
+                .values
+                .iter_mut()
+                .zip(parameter.gradients.iter())
+                .enumerate()
+            {
+                first[i] = self.beta1 * first[i] + (1. - self.beta1) * gradient;
+                second[i] = self.beta2 * second[i] + (1. - self.beta2) * gradient.powi(2);
+                let m = first[i] / correction1;
+                let v = second[i] / correction2;
+                *value -= self.learning_rate
+                    * (m / (v.sqrt() + self.epsilon) + self.weight_decay * *value);
+            }
+        }
+    }
+
+    fn learning_rate(&self) -> f32 {
+        self.learning_rate
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f32) {
+        self.learning_rate = learning_rate;
+    }
+}
+
+/// Adam with weight decay applied directly to the weights rather than
+/// through the gradients.
+pub struct AdamW {
+    adam: Adam,
+}
+
+impl AdamW {
+    pub fn new(learning_rate: f32, weight_decay: f32) -> Self {
+        let mut adam = Adam::new(learning_rate);
+        adam.weight_decay = weight_decay;
+        Self { adam }
+    }
+
+    pub fn with_betas(self, beta1: f32, beta2: f32) -> Self {
+        Self {
+            adam: self.adam.with_betas(beta1, beta2),
+        }
+    }
+}
+
+impl Optimizer for AdamW {
+    fn step(&mut self, parameters: &mut [Parameter]) {
+        self.adam.step(parameters);
+    }
+
+    fn learning_rate(&self) -> f32 {
+        self.adam.learning_rate
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f32) {
+        self.adam.learning_rate = learning_rate;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Minimises (x - 3)^2 starting at x = 0 and returns the final x.
+    fn minimise(optimizer: &mut dyn Optimizer, steps: usize) -> f32 {
+        let mut values = vec![0.];
+        for _ in 0..steps {
+            let mut gradients = vec![2. * (values[0] - 3.)];
+            optimizer.step(&mut [Parameter {
+                values: &mut values,
+                gradients: &mut gradients,
+            }]);
+        }
+        values[0]
+    }
+
+    #[test]
+    fn sgd_step() {
+        let mut values = vec![1., 2.];
+        let mut gradients = vec![0.5, -1.];
+        Sgd::new(0.1).step(&mut [Parameter {
+            values: &mut values,
+            gradients: &mut gradients,
+        }]);
+        assert_eq!(values, vec![0.95, 2.1]);
+    }
+
+    #[test]
+    fn sgd_momentum_accumulates() {
+        let mut sgd = Sgd::new(0.1).with_momentum(0.9);
+        let mut values = vec![0.];
+        for _ in 0..2 {
+            sgd.step(&mut [Parameter {
+                values: &mut values,
+                gradients: &mut [1.],
+            }]);
+        }
+        assert!((values[0] + 0.29).abs() < 1e-6);
+    }
+
+    #[test]
+    fn optimizers_converge() {
+        let optimizers: Vec<Box<dyn Optimizer>> = vec![
+            Box::new(Sgd::new(0.1)),
+            Box::new(Sgd::new(0.05).with_momentum(0.9)),
+            Box::new(Sgd::new(0.05).with_nesterov(0.9)),
+            Box::new(RmsProp::new(0.01)),
+            Box::new(Adagrad::new(0.5)),
+            Box::new(Adam::new(0.1)),
+            Box::new(AdamW::new(0.1, 0.001)),
+        ];
+        for mut optimizer in optimizers {
+            let x = minimise(optimizer.as_mut(), 1000);
+            assert!((x - 3.).abs() < 0.05, "ended at {}", x);
+        }
+    }
+
+    #[test]
+    fn adam_first_step_is_learning_rate() {
+        let mut adam = Adam::new(0.01);
+        let mut values = vec![1., 1.];
+        adam.step(&mut [Parameter {
+            values: &mut values,
+            gradients: &mut [5., -0.1],
+        }]);
+        assert!((values[0] - 0.99).abs() < 1e-6);
+        assert!((values[1] - 1.01).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,368 @@
+use crate::metrics::argmax;
+use crate::preprocessing::Transformer;
+use crate::rann::Rann;
+use crate::serialization::{binary, LayerRecord, ModelError, Registry};
+use std::fs;
+use std::path::Path;
+
+/// Turns the outputs of the model into the prediction of a `Pipeline`.
+pub enum PostProcessor {
+    /// The index of the largest output, as a single value.
+    Argmax,
+    /// The inverse of a transformer fitted on the targets, such as a scaler
+    /// to get back to target units or an encoder to get back labels.
+    InverseTransform(Box<dyn Transformer>),
+}
+
+impl PostProcessor {
+    pub fn apply(&self, outputs: &[f32]) -> Vec<f32> {
+        match self {
+            PostProcessor::Argmax => vec![argmax(outputs) as f32],
+            PostProcessor::InverseTransform(transformer) => transformer.inverse_transform(outputs),
+        }
+    }
+
+    fn to_record(&self) -> Result<LayerRecord, ModelError> {
+        Ok(match self {
+            PostProcessor::Argmax => LayerRecord::new("argmax", 0, 0),
+            PostProcessor::InverseTransform(transformer) => {
+                let mut record = LayerRecord::new("inverse_transform", 0, 0);
+                record.layers.push(transformer.to_record()?);
+                record
+            }
+        })
+    }
+
+    fn from_record(record: &LayerRecord, registry: &Registry) -> Result<Self, ModelError> {
+        match (record.kind.as_str(), record.layers.first()) {
+            ("argmax", _) => Ok(PostProcessor::Argmax),
+            ("inverse_transform", Some(transformer)) => Ok(PostProcessor::InverseTransform(
+                registry.transformer(transformer)?,
+            )),
+            _ => Err(ModelError::Corrupt(format!(
+                "{} is not a post-processor",
+                record.kind
+            ))),
+        }
+    }
+}
+
+/// Fitted transformers, a trained model and an optional post-processor that
+/// turn a raw row into a prediction, and that are saved as a single file so
+/// that serving applies exactly the preprocessing used in training.
+///
+/// ```no_run
+/// use rann::pipeline::{Pipeline, PostProcessor};
+/// use rann::preprocessing::{StandardScaler, Transformer};
+/// use rann::rann::Rann;
+///
+/// # let (rows, model) = (vec![vec![0.; 4]], Rann::new(&[4, 8, 3]));
+/// let mut scaler = StandardScaler::new();
+/// scaler.fit(&rows);
+/// let pipeline = Pipeline::new(model)
+///     .with_transformer(scaler)
+///     .with_post_processor(PostProcessor::Argmax);
+/// pipeline.save("model.rann").unwrap();
+///
+/// let mut served = Pipeline::load("model.rann").unwrap();
+/// let class = served.predict(&[5.1, 3.5, 1.4, 0.2])[0];
+/// ```
+pub struct Pipeline {
+    transformers: Vec<Box<dyn Transformer>>,
+    model: Rann,
+    post_processor: Option<PostProcessor>,
+}
+
+impl Pipeline {
+    pub fn new<M: Into<Rann>>(model: M) -> Self {
+        Self {
+            transformers: vec![],
+            model: model.into(),
+            post_processor: None,
+        }
+    }
+
+    /// Appends a fitted transformer; rows go through the transformers in the
+    /// order they were added.
+    pub fn with_transformer<T: Transformer + 'static>(mut self, transformer: T) -> Self {
+        self.transformers.push(Box::new(transformer));
+        self
+    }
+
+    pub fn with_post_processor(mut self, post_processor: PostProcessor) -> Self {
+        self.post_processor = Some(post_processor);
+        self
+    }
+
+    pub fn model(&self) -> &Rann {
+        &self.model
+    }
+
+    pub fn model_mut(&mut self) -> &mut Rann {
+        &mut self.model
+    }
+
+    /// `row` after all transformers, as the model sees it.
+    pub fn transform(&self, row: &[f32]) -> Vec<f32> {
+        self.transformers
+            .iter()
+            .fold(row.to_vec(), |row, transformer| transformer.transform(&row))
+    }
+
+    pub fn predict(&mut self, row: &[f32]) -> Vec<f32> {
+        let outputs = self.model.forward(&self.transform(row));
+        match &self.post_processor {
+            Some(post_processor) => post_processor.apply(&outputs),
+            None => outputs,
+        }
+    }
+
+    pub fn predict_all(&mut self, rows: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        rows.iter().map(|row| self.predict(row)).collect()
+    }
+
+    /// Takes the pipeline apart into its model, transformers and
+    /// post-processor.
+    pub fn into_parts(self) -> (Rann, Vec<Box<dyn Transformer>>, Option<PostProcessor>) {
+        (self.model, self.transformers, self.post_processor)
+    }
+
+    pub fn to_record(&self) -> Result<LayerRecord, ModelError> {
+        let transformers: Vec<&dyn Transformer> = self
+            .transformers
+            .iter()
+            .map(|transformer| transformer.as_ref())
+            .collect();
+        record(&self.model, &transformers, self.post_processor.as_ref())
+    }
+
+    /// Builds a pipeline from a record made by `to_record`. The model gets the
+    /// default loss and optimizer, as with `Rann::from_record`. Records of the
+    /// older "preprocessed" kind, a model followed by its transformers, are
+    /// read as a pipeline without post-processor.
+    pub fn from_record(record: &LayerRecord, registry: &Registry) -> Result<Self, ModelError> {
+        let (model, transformers, post_processor) = match record.kind.as_str() {
+            "pipeline" if record.layers.len() >= 2 => (
+                &record.layers[0],
+                &record.layers[1].layers[..],
+                record.layers.get(2),
+            ),
+            "preprocessed" if !record.layers.is_empty() => {
+                (&record.layers[0], &record.layers[1..], None)
+            }
+            "pipeline" | "preprocessed" => {
+                return Err(ModelError::Corrupt(format!(
+                    "{} has no network",
+                    record.kind
+                )))
+            }
+            _ => {
+                return Err(ModelError::InvalidFormat(format!(
+                    "expected a pipeline, found {}",
+                    record.kind
+                )))
+            }
+        };
+        if Self::is_pipeline(model) {
+            return Err(ModelError::Corrupt(format!(
+                "{} holds another {}",
+                record.kind, model.kind
+            )));
+        }
+        let transformers = transformers
+            .iter()
+            .map(|transformer| registry.transformer(transformer))
+            .collect::<Result<Vec<Box<dyn Transformer>>, ModelError>>()?;
+        let post_processor = match post_processor {
+            Some(post_processor) => Some(PostProcessor::from_record(post_processor, registry)?),
+            None => None,
+        };
+        Ok(Self {
+            transformers,
+            model: Rann::from_record(model, registry)?,
+            post_processor,
+        })
+    }
+
+    /// Whether `record` holds a pipeline rather than a bare model.
+    pub fn is_pipeline(record: &LayerRecord) -> bool {
+        record.kind == "pipeline" || record.kind == "preprocessed"
+    }
+
+    /// Writes transformers, model and post-processor to one file in the
+    /// binary format of `serialization::binary`. `Rann::load` reads the model
+    /// alone back from it.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ModelError> {
+        fs::write(path, binary::encode(&self.to_record()?))?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ModelError> {
+        Self::load_with_registry(path, &Registry::default())
+    }
+
+    pub fn load_with_registry<P: AsRef<Path>>(
+        path: P,
+        registry: &Registry,
+    ) -> Result<Self, ModelError> {
+        Self::from_record(&binary::decode(&fs::read(path)?)?, registry)
+    }
+}
+
+// The record of a pipeline: the model, the transformers and, if there is
+// one, the post-processor. `Rann::save_with_preprocessors` writes the same.
+pub(crate) fn record(
+    model: &Rann,
+    transformers: &[&dyn Transformer],
+    post_processor: Option<&PostProcessor>,
+) -> Result<LayerRecord, ModelError> {
+    let mut record = LayerRecord::new("pipeline", 0, 0);
+    record.layers.push(model.to_record()?);
+    let mut list = LayerRecord::new("transformers", 0, 0);
+    for transformer in transformers {
+        list.layers.push(transformer.to_record()?);
+    }
+    record.layers.push(list);
+    if let Some(post_processor) = post_processor {
+        record.layers.push(post_processor.to_record()?);
+    }
+    Ok(record)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::activations::Activation::*;
+    use crate::node::RannV2;
+    use crate::optimizers::Adam;
+    use crate::preprocessing::{MinMaxScaler, OneHotEncoder, StandardScaler};
+    use crate::testing::temp;
+    use crate::training::FitConfig;
+
+    #[test]
+    fn regression_pipeline() {
+        // y = x0 - x1 on inputs and targets in the hundreds
+        let rows: Vec<Vec<f32>> = (0..40)
+            .map(|i| vec![(i * 37 % 100) as f32 * 10., 500. + (i * 11 % 50) as f32])
+            .collect();
+        let targets: Vec<Vec<f32>> = rows.iter().map(|row| vec![row[0] - row[1]]).collect();
+        let mut inputs = StandardScaler::new();
+        let mut outputs = MinMaxScaler::new();
+        let data = (inputs.fit_transform(&rows), outputs.fit_transform(&targets));
+        let mut rann = Rann::with_activations(&[(2, Linear), (1, Linear)])
+            .with_optimizer(Adam::new(0.05))
+            .with_seed(2);
+        rann.fit(
+            &data,
+            FitConfig {
+                epochs: 100,
+                batch_size: 4,
+                ..FitConfig::default()
+            },
+        );
+
+        let mut pipeline = Pipeline::new(rann)
+            .with_transformer(inputs)
+            .with_post_processor(PostProcessor::InverseTransform(Box::new(outputs)));
+        let prediction = pipeline.predict(&[800., 520.])[0];
+        assert!((prediction - 280.).abs() < 10., "{}", prediction);
+
+        let path = temp("pipeline_regression.rann");
+        pipeline.save(&path).unwrap();
+        let mut loaded = Pipeline::load(&path).unwrap();
+        let mut model = Rann::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.predict_all(&rows), pipeline.predict_all(&rows));
+        let input = pipeline.transform(&rows[0]);
+        assert_eq!(model.forward(&input), pipeline.model_mut().forward(&input));
+    }
+
+    #[test]
+    fn classification_pipeline() {
+        let mut encoder = OneHotEncoder::new().with_columns(&[1]);
+        encoder.fit(&[vec![0., 3.], vec![0., 5.]]);
+        let rannv2 = RannV2::new(vec![3, 4, 2]).with_seed(1);
+        let mut pipeline = Pipeline::new(rannv2)
+            .with_transformer(encoder)
+            .with_post_processor(PostProcessor::Argmax);
+        // the seeded model puts these two rows, differing only in the
+        // encoded column, in different classes
+        let mut model = RannV2::new(vec![3, 4, 2]).with_seed(1);
+        for (row, encoded) in [([0., 5.], [0., 0., 1.]), ([0., 3.], [0., 1., 0.])].iter() {
+            let class = crate::metrics::argmax(&model.forward(encoded)) as f32;
+            assert_eq!(pipeline.predict(row), vec![class]);
+        }
+        assert_eq!(pipeline.predict(&[0., 5.]), vec![0.]);
+        assert_eq!(pipeline.predict(&[0., 3.]), vec![1.]);
+        let class = pipeline.predict(&[0.5, 5.]);
+
+        let path = temp("pipeline_classification.rann");
+        pipeline.save(&path).unwrap();
+        let mut loaded = Pipeline::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.predict(&[0.5, 5.]), class);
+        assert_eq!(loaded.transform(&[0.5, 5.]), vec![0.5, 0., 1.]);
+
+        let mut labels = OneHotEncoder::new();
+        labels.fit(&[vec![10.], vec![20.]]);
+        let post_processor = PostProcessor::InverseTransform(Box::new(labels));
+        assert_eq!(post_processor.apply(&[0.3, 0.7]), vec![20.]);
+    }
+
+    #[test]
+    fn cross_load() {
+        let rows = vec![vec![1., 3.], vec![2., 5.], vec![4., 4.]];
+        let mut scaler = StandardScaler::new();
+        scaler.fit(&rows);
+        let mut rann = Rann::new(&[2, 3, 2]).with_seed(6);
+        let expected: Vec<Vec<f32>> = rows
+            .iter()
+            .map(|row| rann.forward(&scaler.transform(row)))
+            .collect();
+
+        let path = temp("pipeline_cross_load_preprocessors.rann");
+        rann.save_with_preprocessors(&path, &[&scaler]).unwrap();
+        let mut loaded = Pipeline::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.predict_all(&rows), expected);
+
+        let path = temp("pipeline_cross_load_pipeline.rann");
+        let pipeline = Pipeline::new(rann).with_transformer(scaler);
+        pipeline.save(&path).unwrap();
+        let (mut model, transformers) = Rann::load_with_preprocessors(&path).unwrap();
+        assert_eq!(transformers.len(), 1);
+        let input = transformers[0].transform(&rows[0]);
+        assert_eq!(model.forward(&input), expected[0]);
+
+        pipeline
+            .with_post_processor(PostProcessor::Argmax)
+            .save(&path)
+            .unwrap();
+        let result = Rann::load_with_preprocessors(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(ModelError::Unsupported(_))));
+    }
+
+    #[test]
+    fn load_errors() {
+        let path = temp("pipeline_errors.rann");
+        Rann::new(&[2, 2]).save(&path).unwrap();
+        let result = Pipeline::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(ModelError::InvalidFormat(_))));
+
+        let mut nested = LayerRecord::new("pipeline", 0, 0);
+        assert!(matches!(
+            Pipeline::from_record(&nested, &Registry::default()),
+            Err(ModelError::Corrupt(_))
+        ));
+        nested
+            .layers
+            .push(Pipeline::new(Rann::new(&[2, 2])).to_record().unwrap());
+        nested.layers.push(LayerRecord::new("transformers", 0, 0));
+        assert!(matches!(
+            Pipeline::from_record(&nested, &Registry::default()),
+            Err(ModelError::Corrupt(_))
+        ));
+    }
+}
@@ -0,0 +1,638 @@
+//! Preprocessors that learn a transform of feature rows from training data
+//! and apply it, or its inverse, to any row afterwards.
+
+use crate::serialization::{LayerRecord, ModelError};
+use crate::tensor::Tensor;
+
+pub trait Transformer {
+    /// Learns the parameters of the transform from `rows`.
+    fn fit(&mut self, rows: &[Vec<f32>]);
+    fn transform(&self, row: &[f32]) -> Vec<f32>;
+    fn inverse_transform(&self, row: &[f32]) -> Vec<f32>;
+
+    fn fit_transform(&mut self, rows: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        self.fit(rows);
+        self.transform_all(rows)
+    }
+
+    fn transform_all(&self, rows: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        rows.iter().map(|row| self.transform(row)).collect()
+    }
+
+    fn inverse_transform_all(&self, rows: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        rows.iter().map(|row| self.inverse_transform(row)).collect()
+    }
+
+    /// Describes the fitted transform so it can be saved next to a model, see
+    /// `Rann::save_with_preprocessors`.
+    fn to_record(&self) -> Result<LayerRecord, ModelError> {
+        Err(ModelError::Unsupported(
+            "transformer does not implement to_record".to_string(),
+        ))
+    }
+}
+
+fn columns(rows: &[Vec<f32>]) -> Vec<Vec<f32>> {
+    let width = rows.first().map_or(0, |row| row.len());
+    (0..width)
+        .map(|i| rows.iter().map(|row| row[i]).collect())
+        .collect()
+}
+
+// A zero spread would divide by zero; such columns are only shifted.
+fn nonzero(scale: f32) -> f32 {
+    if scale == 0. {
+        1.
+    } else {
+        scale
+    }
+}
+
+fn record_of(kind: &str, width: usize, tensors: &[(&str, &[f32])]) -> LayerRecord {
+    let mut record = LayerRecord::new(kind, width, width);
+    for (name, values) in tensors {
+        record
+            .tensors
+            .push((name.to_string(), Tensor::vector(values)));
+    }
+    record
+}
+
+fn tensor_of(record: &LayerRecord, name: &str) -> Result<Vec<f32>, ModelError> {
+    Ok(record
+        .expect_tensor(name, &[record.inputs])?
+        .data()
+        .to_vec())
+}
+
+// Shifts every column by `center` and divides it by `scale`.
+fn affine(row: &[f32], center: &[f32], scale: &[f32]) -> Vec<f32> {
+    assert_eq!(
+        row.len(),
+        center.len(),
+        "transformer is not fitted to rows this wide"
+    );
+    row.iter()
+        .zip(center.iter().zip(scale))
+        .map(|(x, (c, s))| (x - c) / s)
+        .collect()
+}
+
+fn inverse_affine(row: &[f32], center: &[f32], scale: &[f32]) -> Vec<f32> {
+    assert_eq!(
+        row.len(),
+        center.len(),
+        "transformer is not fitted to rows this wide"
+    );
+    row.iter()
+        .zip(center.iter().zip(scale))
+        .map(|(x, (c, s))| x * s + c)
+        .collect()
+}
+
+/// Scales every column to zero mean and unit variance.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StandardScaler {
+    mean: Vec<f32>,
+    std: Vec<f32>,
+}
+
+impl StandardScaler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_record(record: &LayerRecord) -> Result<Self, ModelError> {
+        Ok(Self {
+            mean: tensor_of(record, "mean")?,
+            std: tensor_of(record, "std")?,
+        })
+    }
+}
+
+impl Transformer for StandardScaler {
+    fn fit(&mut self, rows: &[Vec<f32>]) {
+        let (mean, std) = columns(rows)
+            .iter()
+            .map(|column| {
+                let n = column.len() as f32;
+                let mean = column.iter().sum::<f32>() / n;
+                let variance = column.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / n;
+                (mean, nonzero(variance.sqrt()))
+            })
+            .unzip();
+        self.mean = mean;
+        self.std = std;
+    }
+
+    fn transform(&self, row: &[f32]) -> Vec<f32> {
+        affine(row, &self.mean, &self.std)
+    }
+
+    fn inverse_transform(&self, row: &[f32]) -> Vec<f32> {
+        inverse_affine(row, &self.mean, &self.std)
+    }
+
+    fn to_record(&self) -> Result<LayerRecord, ModelError> {
+        Ok(record_of(
+            "standard_scaler",
+            self.mean.len(),
+            &[("mean", &self.mean), ("std", &self.std)],
+        ))
+    }
+}
+
+/// Scales every column linearly so that its training values span [0, 1],
+/// or another range set with `with_range`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MinMaxScaler {
+    range: (f32, f32),
+    min: Vec<f32>,
+    // the column span per unit of the target range
+    scale: Vec<f32>,
+}
+
+impl MinMaxScaler {
+    pub fn new() -> Self {
+        Self {
+            range: (0., 1.),
+            min: vec![],
+            scale: vec![],
+        }
+    }
+
+    pub fn with_range(mut self, low: f32, high: f32) -> Self {
+        self.range = (low, high);
+        self
+    }
+
+    pub fn from_record(record: &LayerRecord) -> Result<Self, ModelError> {
+        let range = record.expect_tensor("range", &[2])?.data();
+        Ok(Self {
+            range: (range[0], range[1]),
+            min: tensor_of(record, "min")?,
+            scale: tensor_of(record, "scale")?,
+        })
+    }
+}
+
+impl Default for MinMaxScaler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transformer for MinMaxScaler {
+    fn fit(&mut self, rows: &[Vec<f32>]) {
+        let (low, high) = self.range;
+        let (min, scale) = columns(rows)
+            .iter()
+            .map(|column| {
+                let min = column.iter().cloned().fold(f32::INFINITY, f32::min);
+                let max = column.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+                (min, nonzero(max - min) / nonzero(high - low))
+            })
+            .unzip();
+        self.min = min;
+        self.scale = scale;
+    }
+
+    fn transform(&self, row: &[f32]) -> Vec<f32> {
+        let low = self.range.0;
+        affine(row, &self.min, &self.scale)
+            .into_iter()
+            .map(|x| x + low)
+            .collect()
+    }
+
+    fn inverse_transform(&self, row: &[f32]) -> Vec<f32> {
+        let shifted: Vec<f32> = row.iter().map(|x| x - self.range.0).collect();
+        inverse_affine(&shifted, &self.min, &self.scale)
+    }
+
+    fn to_record(&self) -> Result<LayerRecord, ModelError> {
+        let mut record = record_of(
+            "min_max_scaler",
+            self.min.len(),
+            &[("min", &self.min), ("scale", &self.scale)],
+        );
+        record.tensors.push((
+            "range".to_string(),
+            Tensor::vector(&[self.range.0, self.range.1]),
+        ));
+        Ok(record)
+    }
+}
+
+// Linearly interpolated quantile of sorted values.
+fn quantile(sorted: &[f32], q: f32) -> f32 {
+    let position = q * (sorted.len() - 1) as f32;
+    let (below, above) = (position.floor() as usize, position.ceil() as usize);
+    sorted[below] + (sorted[above] - sorted[below]) * (position - below as f32)
+}
+
+/// Centers every column on its median and scales it by its interquartile
+/// range, which outliers hardly move.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RobustScaler {
+    median: Vec<f32>,
+    iqr: Vec<f32>,
+}
+
+impl RobustScaler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_record(record: &LayerRecord) -> Result<Self, ModelError> {
+        Ok(Self {
+            median: tensor_of(record, "median")?,
+            iqr: tensor_of(record, "iqr")?,
+        })
+    }
+}
+
+impl Transformer for RobustScaler {
+    fn fit(&mut self, rows: &[Vec<f32>]) {
+        let (median, iqr) = columns(rows)
+            .into_iter()
+            .map(|mut column| {
+                column.sort_by(f32::total_cmp);
+                let iqr = quantile(&column, 0.75) - quantile(&column, 0.25);
+                (quantile(&column, 0.5), nonzero(iqr))
+            })
+            .unzip();
+        self.median = median;
+        self.iqr = iqr;
+    }
+
+    fn transform(&self, row: &[f32]) -> Vec<f32> {
+        affine(row, &self.median, &self.iqr)
+    }
+
+    fn inverse_transform(&self, row: &[f32]) -> Vec<f32> {
+        inverse_affine(row, &self.median, &self.iqr)
+    }
+
+    fn to_record(&self) -> Result<LayerRecord, ModelError> {
+        Ok(record_of(
+            "robust_scaler",
+            self.median.len(),
+            &[("median", &self.median), ("iqr", &self.iqr)],
+        ))
+    }
+}
+
+/// Scales every row to unit L2 norm. Nothing is learned, and as the norm is
+/// lost the inverse transform returns rows unchanged.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Normalizer {}
+
+impl Normalizer {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Transformer for Normalizer {
+    fn fit(&mut self, _rows: &[Vec<f32>]) {}
+
+    fn transform(&self, row: &[f32]) -> Vec<f32> {
+        let norm = nonzero(row.iter().map(|x| x * x).sum::<f32>().sqrt());
+        row.iter().map(|x| x / norm).collect()
+    }
+
+    fn inverse_transform(&self, row: &[f32]) -> Vec<f32> {
+        row.to_vec()
+    }
+
+    fn to_record(&self) -> Result<LayerRecord, ModelError> {
+        Ok(LayerRecord::new("normalizer", 0, 0))
+    }
+}
+
+// The sorted distinct values of each of `columns`, or of every column.
+fn fit_categories(rows: &[Vec<f32>], columns_to_encode: &Option<Vec<usize>>) -> Vec<Vec<f32>> {
+    columns(rows)
+        .into_iter()
+        .enumerate()
+        .map(|(i, mut column)| match columns_to_encode {
+            Some(encoded) if !encoded.contains(&i) => vec![],
+            _ => {
+                column.sort_by(f32::total_cmp);
+                column.dedup();
+                column
+            }
+        })
+        .collect()
+}
+
+// One tensor of categories per column, empty for columns passed through.
+fn categories_record(
+    kind: &str,
+    categories: &[Vec<f32>],
+    outputs: usize,
+    columns: &Option<Vec<usize>>,
+) -> LayerRecord {
+    let mut record = LayerRecord::new(kind, categories.len(), outputs);
+    for (i, column) in categories.iter().enumerate() {
+        record
+            .tensors
+            .push((format!("categories.{}", i), Tensor::vector(column)));
+    }
+    if let Some(columns) = columns {
+        let columns: Vec<f32> = columns.iter().map(|c| *c as f32).collect();
+        record
+            .tensors
+            .push(("columns".to_string(), Tensor::vector(&columns)));
+    }
+    record
+}
+
+type Categories = (Vec<Vec<f32>>, Option<Vec<usize>>);
+
+fn categories_from_record(record: &LayerRecord) -> Result<Categories, ModelError> {
+    let categories = (0..record.inputs)
+        .map(|i| {
+            let name = format!("categories.{}", i);
+            match record.tensor(&name) {
+                Some(tensor) if tensor.rank() == 1 => Ok(tensor.data().to_vec()),
+                _ => Err(ModelError::Corrupt(format!(
+                    "{} is missing {}",
+                    record.kind, name
+                ))),
+            }
+        })
+        .collect::<Result<Vec<Vec<f32>>, ModelError>>()?;
+    let columns = record
+        .tensor("columns")
+        .map(|columns| columns.data().iter().map(|c| *c as usize).collect());
+    Ok((categories, columns))
+}
+
+fn assert_fitted_width(row: &[f32], categories: &[Vec<f32>]) {
+    assert_eq!(
+        row.len(),
+        categories.len(),
+        "transformer is not fitted to rows this wide"
+    );
+}
+
+fn position(categories: &[f32], value: f32) -> Option<usize> {
+    categories.iter().position(|category| *category == value)
+}
+
+/// Replaces every categorical column by one output per category seen in
+/// training, 1 for the category of the row and 0 for the others. Values not
+/// seen in training encode as all zeros. All columns are categorical unless
+/// chosen with `with_columns`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OneHotEncoder {
+    columns: Option<Vec<usize>>,
+    // per input column, empty if it isn't encoded
+    categories: Vec<Vec<f32>>,
+}
+
+impl OneHotEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_columns(mut self, columns: &[usize]) -> Self {
+        self.columns = Some(columns.to_vec());
+        self
+    }
+
+    pub fn categories(&self, column: usize) -> &[f32] {
+        &self.categories[column]
+    }
+
+    pub fn from_record(record: &LayerRecord) -> Result<Self, ModelError> {
+        let (categories, columns) = categories_from_record(record)?;
+        Ok(Self {
+            columns,
+            categories,
+        })
+    }
+}
+
+impl Transformer for OneHotEncoder {
+    fn fit(&mut self, rows: &[Vec<f32>]) {
+        self.categories = fit_categories(rows, &self.columns);
+    }
+
+    fn transform(&self, row: &[f32]) -> Vec<f32> {
+        assert_fitted_width(row, &self.categories);
+        let mut encoded = vec![];
+        for (value, categories) in row.iter().zip(&self.categories) {
+            if categories.is_empty() {
+                encoded.push(*value);
+                continue;
+            }
+            let hot = position(categories, *value);
+            encoded.extend((0..categories.len()).map(|i| if hot == Some(i) { 1. } else { 0. }));
+        }
+        encoded
+    }
+
+    /// Every category decodes to the category with the largest output.
+    fn inverse_transform(&self, row: &[f32]) -> Vec<f32> {
+        let mut decoded = vec![];
+        let mut offset = 0;
+        for categories in &self.categories {
+            if categories.is_empty() {
+                decoded.push(row[offset]);
+                offset += 1;
+                continue;
+            }
+            let outputs = &row[offset..offset + categories.len()];
+            decoded.push(categories[crate::metrics::argmax(outputs)]);
+            offset += categories.len();
+        }
+        decoded
+    }
+
+    fn to_record(&self) -> Result<LayerRecord, ModelError> {
+        let outputs = self.categories.iter().map(|c| c.len().max(1)).sum();
+        Ok(categories_record(
+            "one_hot_encoder",
+            &self.categories,
+            outputs,
+            &self.columns,
+        ))
+    }
+}
+
+/// Replaces the values of every categorical column by their index among the
+/// sorted categories seen in training, or -1 for values not seen. All columns
+/// are categorical unless chosen with `with_columns`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OrdinalEncoder {
+    columns: Option<Vec<usize>>,
+    categories: Vec<Vec<f32>>,
+}
+
+impl OrdinalEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_columns(mut self, columns: &[usize]) -> Self {
+        self.columns = Some(columns.to_vec());
+        self
+    }
+
+    pub fn categories(&self, column: usize) -> &[f32] {
+        &self.categories[column]
+    }
+
+    pub fn from_record(record: &LayerRecord) -> Result<Self, ModelError> {
+        let (categories, columns) = categories_from_record(record)?;
+        Ok(Self {
+            columns,
+            categories,
+        })
+    }
+}
+
+impl Transformer for OrdinalEncoder {
+    fn fit(&mut self, rows: &[Vec<f32>]) {
+        self.categories = fit_categories(rows, &self.columns);
+    }
+
+    fn transform(&self, row: &[f32]) -> Vec<f32> {
+        assert_fitted_width(row, &self.categories);
+        row.iter()
+            .zip(&self.categories)
+            .map(|(value, categories)| match categories.is_empty() {
+                true => *value,
+                false => position(categories, *value).map_or(-1., |i| i as f32),
+            })
+            .collect()
+    }
+
+    /// Indices are rounded to the nearest category.
+    fn inverse_transform(&self, row: &[f32]) -> Vec<f32> {
+        row.iter()
+            .zip(&self.categories)
+            .map(|(value, categories)| match categories.is_empty() {
+                true => *value,
+                false => {
+                    let index = value.round().clamp(0., (categories.len() - 1) as f32);
+                    categories[index as usize]
+                }
+            })
+            .collect()
+    }
+
+    fn to_record(&self) -> Result<LayerRecord, ModelError> {
+        Ok(categories_record(
+            "ordinal_encoder",
+            &self.categories,
+            self.categories.len(),
+            &self.columns,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::serialization::Registry;
+    use crate::testing::all_close;
+
+    fn rows() -> Vec<Vec<f32>> {
+        vec![
+            vec![1., 10., 3.],
+            vec![2., 10., 1.],
+            vec![3., 10., 3.],
+            vec![10., 10., 2.],
+        ]
+    }
+
+    // `transformer` undoes itself, and survives a trip through a record
+    fn round_trip<T: Transformer>(transformer: &T) {
+        let record = transformer.to_record().unwrap();
+        let loaded = Registry::default().transformer(&record).unwrap();
+        for row in rows() {
+            let transformed = transformer.transform(&row);
+            assert!(all_close(&loaded.transform(&row), &transformed));
+            assert!(all_close(
+                &transformer.inverse_transform(&transformed),
+                &row
+            ));
+        }
+    }
+
+    #[test]
+    fn scalers() {
+        let mut standard = StandardScaler::new();
+        let scaled = standard.fit_transform(&rows());
+        assert!(all_close(&scaled[0][..2], &[-0.848_528_1, 0.]));
+        let mean: f32 = scaled.iter().map(|row| row[0]).sum();
+        assert!(mean.abs() < 1e-5);
+        round_trip(&standard);
+
+        let mut min_max = MinMaxScaler::new().with_range(-1., 1.);
+        let scaled = min_max.fit_transform(&rows());
+        assert!(all_close(&scaled[0], &[-1., -1., 1.]));
+        assert!(all_close(&scaled[3], &[1., -1., 0.]));
+        round_trip(&min_max);
+
+        let mut robust = RobustScaler::new();
+        robust.fit(&rows());
+        // column 0: median 2.5, quartiles 1.75 and 4.75
+        assert!(all_close(
+            &robust.transform(&[5.5, 10., 2.5]),
+            &[1., 0., 0.]
+        ));
+        round_trip(&robust);
+
+        let mut with_nan = rows();
+        with_nan[1][1] = f32::NAN;
+        RobustScaler::new().fit(&with_nan);
+        OrdinalEncoder::new().fit(&with_nan);
+
+        let normalizer = Normalizer::new();
+        assert!(all_close(&normalizer.transform(&[3., 4.]), &[0.6, 0.8]));
+        assert!(all_close(&normalizer.transform(&[0., 0.]), &[0., 0.]));
+    }
+
+    #[test]
+    fn encoders() {
+        let mut one_hot = OneHotEncoder::new().with_columns(&[2]);
+        one_hot.fit(&rows());
+        assert_eq!(one_hot.categories(2), [1., 2., 3.]);
+        assert_eq!(one_hot.transform(&[7., 8., 2.]), vec![7., 8., 0., 1., 0.]);
+        assert_eq!(one_hot.transform(&[7., 8., 5.]), vec![7., 8., 0., 0., 0.]);
+        assert_eq!(
+            one_hot.inverse_transform(&[7., 8., 0.2, 0.1, 0.7]),
+            vec![7., 8., 3.]
+        );
+        round_trip(&one_hot);
+
+        let mut ordinal = OrdinalEncoder::new();
+        let encoded = ordinal.fit_transform(&rows());
+        assert_eq!(encoded[3], vec![3., 0., 1.]);
+        assert_eq!(ordinal.transform(&[4., 10., 3.]), vec![-1., 0., 2.]);
+        round_trip(&ordinal);
+
+        let record = OneHotEncoder::new().to_record().unwrap();
+        let mut corrupt = record.clone();
+        corrupt.inputs = 1;
+        assert!(Registry::default().transformer(&corrupt).is_err());
+        corrupt.kind = "unknown".to_string();
+        assert!(matches!(
+            Registry::default().transformer(&corrupt),
+            Err(ModelError::UnknownTransformer(_))
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "not fitted to rows this wide")]
+    fn encoder_width() {
+        let mut one_hot = OneHotEncoder::new().with_columns(&[2]);
+        one_hot.fit(&rows());
+        one_hot.transform(&[7., 2.]);
+    }
+}
@@ -1,69 +1,453 @@
-use crate::activations::{ActivationFunc, Sigmoid, RELU};
+use crate::activations::Activation;
+use crate::callbacks::Control;
+use crate::data::loader::DataLoader;
+use crate::data::{Dataset, Subset};
 use crate::layers::dense::LayerDense;
+use crate::layers::Layer;
+use crate::losses::{Loss, MeanSquaredError};
+use crate::metrics::Metric;
+use crate::node::LayerSoftmax;
+use crate::optimizers::{Optimizer, Sgd};
+use crate::pipeline::{self, Pipeline};
+use crate::preprocessing::Transformer;
+use crate::schedulers::Progress;
+use crate::sequential::Sequential;
+use crate::serialization::{binary, onnx, LayerRecord, ModelError, Registry};
+use crate::training::{EpochLog, FitConfig, History, Scores, Validation};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::fs;
+use std::path::Path;
 
 pub struct Rann {
-    layers: Vec<LayerDense>,
+    network: Sequential,
+    loss: Box<dyn Loss>,
+    optimizer: Box<dyn Optimizer>,
+    rng: ChaCha8Rng,
+    output: Vec<f32>,
 }
 
 impl Rann {
-    pub fn new(layer_sizes: &Vec<usize>) -> Self {
-        let mut layers = Vec::new();
-        for i in 0..layer_sizes.len() - 1 {
-            let input_size = layer_sizes[i];
-            let output_size = layer_sizes[i + 1];
-            let r: Box<dyn ActivationFunc>;
-            if i == layer_sizes.len() - 2 {
-                // r = Box::new(RELU::new());
-                r = Box::new(Sigmoid::new());
-            } else {
-                r = Box::new(Sigmoid::new());
+    pub fn new(layer_sizes: &[usize]) -> Self {
+        let layers: Vec<(usize, Activation)> = layer_sizes
+            .iter()
+            .map(|size| (*size, Activation::Sigmoid))
+            .collect();
+        Self::with_activations(&layers)
+    }
+
+    /// Builds a network from `(size, activation)` pairs. The first pair is the
+    /// input layer, so its activation is ignored.
+    pub fn with_activations(layers: &[(usize, Activation)]) -> Self {
+        let mut network = Sequential::new();
+        for pair in layers.windows(2) {
+            let (input_size, _) = pair[0];
+            let (output_size, activation) = pair[1];
+            network = network.add(LayerDense::new(
+                input_size,
+                output_size,
+                activation.element_wise(),
+            ));
+            if activation == Activation::Softmax {
+                network = network.add(LayerSoftmax::new(output_size));
+            }
+        }
+
+        Self::from(network)
+    }
+
+    pub fn with_loss<L: Loss + 'static>(mut self, loss: L) -> Self {
+        self.loss = Box::new(loss);
+        self
+    }
+
+    pub fn with_optimizer<O: Optimizer + 'static>(mut self, optimizer: O) -> Self {
+        self.optimizer = Box::new(optimizer);
+        self
+    }
+
+    /// Re-draws every weight and bias from `seed` and uses it for all later
+    /// randomness, so two models built the same way with the same seed train
+    /// identically. Weights set or loaded before are overwritten, so call it
+    /// right after building the model, never on a trained or loaded one.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+        self.network.initialize(&mut self.rng);
+        self
+    }
+
+    pub fn forward(&mut self, input: &[f32]) -> Vec<f32> {
+        self.output = self.network.forward(input);
+        self.output.clone()
+    }
+
+    /// Backpropagates the loss between the last `forward` output and `target`,
+    /// lets the optimizer update the weights and returns the loss.
+    pub fn backwards(&mut self, target: &[f32]) -> f32 {
+        let cost = self.accumulate(target);
+        self.apply_gradients(1);
+        cost
+    }
+
+    /// Like `backwards`, but only adds to the gradients held by the layers.
+    /// Call `apply_gradients` once every sample of the batch has been seen.
+    pub fn accumulate(&mut self, target: &[f32]) -> f32 {
+        let cost = self.loss.compute(&self.output, target);
+        let grad = self.loss.compute_derivative(&self.output, target);
+        self.backward(&grad);
+        cost
+    }
+
+    /// Updates the weights from the gradients averaged over `batch_size`
+    /// samples and clears them for the next batch.
+    pub fn apply_gradients(&mut self, batch_size: usize) {
+        let scale = 1. / batch_size.max(1) as f32;
+        let mut parameters = self.network.parameters();
+        for parameter in parameters.iter_mut() {
+            for gradient in parameter.gradients.iter_mut() {
+                *gradient *= scale;
+            }
+        }
+        self.optimizer.step(&mut parameters);
+        self.network.zero_gradients();
+    }
+
+    /// Runs one optimizer step on the mean loss of the batch and returns
+    /// that loss.
+    pub fn train_batch(&mut self, inputs: &[Vec<f32>], targets: &[Vec<f32>]) -> f32 {
+        let mut cost = 0.;
+        for (input, target) in inputs.iter().zip(targets) {
+            self.forward(input);
+            cost += self.accumulate(target);
+        }
+        self.apply_gradients(inputs.len());
+        cost / inputs.len().max(1) as f32
+    }
+
+    /// Trains for `config.epochs` passes over `dataset` in mini-batches of
+    /// `config.batch_size`, validating after each epoch if asked to. A
+    /// scheduler works from the learning rate the optimizer has when `fit`
+    /// starts, and that rate is put back once training ends.
+    pub fn fit<D: Dataset + ?Sized>(&mut self, dataset: &D, mut config: FitConfig) -> History {
+        let (train, validation) = match config.validation {
+            Validation::Split(fraction) => {
+                let held_out = (dataset.len() as f32 * fraction.clamp(0., 1.)).round() as usize;
+                let split = dataset.len() - held_out;
+                (
+                    Subset::new(dataset, (0..split).collect()),
+                    Some(Subset::new(dataset, (split..dataset.len()).collect())),
+                )
+            }
+            _ => (Subset::new(dataset, (0..dataset.len()).collect()), None),
+        };
+        let validation: Option<&dyn Dataset> = match config.validation {
+            Validation::Data(data) => Some(data),
+            _ => validation.as_ref().map(|subset| subset as &dyn Dataset),
+        };
+
+        let mut history = History::default();
+        let mut loader = DataLoader::new(&train, config.batch_size)
+            .with_shuffle(config.shuffle)
+            .with_seed(self.rng.gen());
+        let batches_per_epoch = loader.len();
+        let base_rate = self.learning_rate();
+        for callback in config.callbacks.iter_mut() {
+            callback.on_train_begin(self);
+        }
+        for epoch in 0..config.epochs {
+            for callback in config.callbacks.iter_mut() {
+                callback.on_epoch_begin(epoch, self);
+            }
+            let mut loss = 0.;
+            let mut predictions = vec![];
+            let mut targets = vec![];
+            for (i, batch) in loader.epoch().enumerate() {
+                if let Some(scheduler) = config.scheduler.as_mut() {
+                    let progress = Progress {
+                        epoch,
+                        batch: i,
+                        batches_per_epoch,
+                    };
+                    self.set_learning_rate(scheduler.learning_rate(base_rate, progress));
+                }
+                let size = batch.len();
+                let mut batch_loss = 0.;
+                for (input, target) in batch.inputs.iter().zip(batch.targets) {
+                    let output = self.forward(input);
+                    batch_loss += self.accumulate(&target);
+                    if !config.metrics.is_empty() {
+                        predictions.push(output);
+                        targets.push(target);
+                    }
+                }
+                self.apply_gradients(size);
+                loss += batch_loss;
+                for callback in config.callbacks.iter_mut() {
+                    callback.on_batch_end(i, batch_loss / size as f32, self);
+                }
+            }
+
+            let log = EpochLog {
+                epoch,
+                train: Scores {
+                    loss: loss / train.len().max(1) as f32,
+                    metrics: compute_metrics(&config.metrics, &predictions, &targets),
+                },
+                validation: validation.map(|data| self.evaluate(data, &config.metrics)),
+                learning_rate: self.learning_rate(),
+            };
+            if let Some(scheduler) = config.scheduler.as_mut() {
+                scheduler.on_epoch_end(&log);
+            }
+            if config.verbose {
+                println!("{}", log);
+            }
+            let mut control = Control::Continue;
+            for callback in config.callbacks.iter_mut() {
+                if callback.on_epoch_end(&log, self) == Control::Stop {
+                    control = Control::Stop;
+                }
             }
+            history.epochs.push(log);
+            if control == Control::Stop {
+                break;
+            }
+        }
+        if config.scheduler.is_some() {
+            self.set_learning_rate(base_rate);
+        }
+        for callback in config.callbacks.iter_mut() {
+            callback.on_train_end(&history, self);
+        }
+        history
+    }
+
+    pub fn learning_rate(&self) -> f32 {
+        self.optimizer.learning_rate()
+    }
+
+    pub fn set_learning_rate(&mut self, learning_rate: f32) {
+        self.optimizer.set_learning_rate(learning_rate);
+    }
+
+    /// A copy of every parameter, in the order of `Layer::parameters`.
+    pub fn weights(&mut self) -> Vec<Vec<f32>> {
+        self.network
+            .parameters()
+            .iter()
+            .map(|parameter| parameter.values.to_vec())
+            .collect()
+    }
 
-            layers.push(LayerDense::new(input_size.clone(), output_size.clone(), r));
+    /// Overwrites the parameters with values taken from `weights`.
+    pub fn set_weights(&mut self, weights: &[Vec<f32>]) {
+        let parameters = self.network.parameters();
+        assert_eq!(parameters.len(), weights.len());
+        for (parameter, values) in parameters.into_iter().zip(weights) {
+            parameter.values.copy_from_slice(values);
         }
+    }
+
+    /// The outputs for every input of `dataset`, in order.
+    pub fn predict<D: Dataset + ?Sized>(&mut self, dataset: &D) -> Vec<Vec<f32>> {
+        (0..dataset.len())
+            .map(|index| self.forward(&dataset.get(index).0))
+            .collect()
+    }
+
+    /// Mean loss and `metrics` over `dataset`, without training.
+    pub fn evaluate<D: Dataset + ?Sized>(
+        &mut self,
+        dataset: &D,
+        metrics: &[Box<dyn Metric>],
+    ) -> Scores {
+        let mut loss = 0.;
+        let mut predictions = Vec::with_capacity(dataset.len());
+        let mut targets = Vec::with_capacity(dataset.len());
+        for index in 0..dataset.len() {
+            let (input, target) = dataset.get(index);
+            let output = self.forward(&input);
+            loss += self.loss.compute(&output, &target);
+            predictions.push(output);
+            targets.push(target);
+        }
+        Scores {
+            loss: loss / dataset.len().max(1) as f32,
+            metrics: compute_metrics(metrics, &predictions, &targets),
+        }
+    }
 
-        Self { layers }
+    pub fn network_mut(&mut self) -> &mut Sequential {
+        &mut self.network
     }
 
-    pub fn forward(&mut self, input: &Vec<f32>) -> Vec<f32> {
-        let mut output = input.clone();
-        for layer in self.layers.iter_mut() {
-            output = layer.forward(&output);
+    fn backward(&mut self, derivatives: &[f32]) {
+        self.network.backwards(derivatives);
+    }
+
+    /// Describes the network, see `serialization::LayerRecord`.
+    pub fn to_record(&self) -> Result<LayerRecord, ModelError> {
+        self.network.to_record()
+    }
+
+    /// Builds a model from a record made by `to_record`. Loss and optimizer
+    /// are not part of the record and start out as the defaults.
+    pub fn from_record(record: &LayerRecord, registry: &Registry) -> Result<Self, ModelError> {
+        if Pipeline::is_pipeline(record) {
+            let model = model_of(record)?;
+            if Pipeline::is_pipeline(model) {
+                return Err(ModelError::Corrupt(format!(
+                    "{} holds another {}",
+                    record.kind, model.kind
+                )));
+            }
+            return Self::from_record(model, registry);
         }
-        output
+        let network = if record.kind == "sequential" {
+            Sequential::from_record(record, registry)?
+        } else {
+            let mut network = Sequential::new();
+            network.push(registry.layer(record)?);
+            network
+        };
+        Ok(Self::from(network))
+    }
+
+    /// Writes the architecture and all weights to `path` in the binary format
+    /// of `serialization::binary`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ModelError> {
+        fs::write(path, binary::encode(&self.to_record()?))?;
+        Ok(())
+    }
+
+    /// Loads a model written by `save`, resolving activations and layers with
+    /// `Registry::default()`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ModelError> {
+        Self::load_with_registry(path, &Registry::default())
+    }
+
+    pub fn load_with_registry<P: AsRef<Path>>(
+        path: P,
+        registry: &Registry,
+    ) -> Result<Self, ModelError> {
+        Self::from_record(&binary::decode(&fs::read(path)?)?, registry)
     }
 
-    fn backward(&mut self, desired_output: &Vec<f32>) {
-        let mut desired_output = desired_output.clone();
-        for layer in self.layers.iter_mut().rev() {
-            desired_output = layer.backward(&desired_output);
+    /// Like `save`, but also writes the fitted transformers that inputs go
+    /// through before reaching the model, in order. The file is the same as
+    /// that of a `Pipeline` without post-processor, so `Pipeline::load` reads
+    /// it too, and `load` still reads the model alone.
+    pub fn save_with_preprocessors<P: AsRef<Path>>(
+        &self,
+        path: P,
+        preprocessors: &[&dyn Transformer],
+    ) -> Result<(), ModelError> {
+        let record = pipeline::record(self, preprocessors, None)?;
+        fs::write(path, binary::encode(&record))?;
+        Ok(())
+    }
+
+    /// Loads a model and its transformers written by `save_with_preprocessors`
+    /// or by `Pipeline::save`. A file written by `save` loads with no
+    /// transformers. A pipeline with a post-processor is an error, as the
+    /// post-processor would be lost; use `Pipeline::load` for those.
+    pub fn load_with_preprocessors<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<(Self, Vec<Box<dyn Transformer>>), ModelError> {
+        Self::load_with_preprocessors_and_registry(path, &Registry::default())
+    }
+
+    pub fn load_with_preprocessors_and_registry<P: AsRef<Path>>(
+        path: P,
+        registry: &Registry,
+    ) -> Result<(Self, Vec<Box<dyn Transformer>>), ModelError> {
+        let record = binary::decode(&fs::read(path)?)?;
+        if !Pipeline::is_pipeline(&record) {
+            return Ok((Self::from_record(&record, registry)?, vec![]));
+        }
+        match Pipeline::from_record(&record, registry)?.into_parts() {
+            (model, transformers, None) => Ok((model, transformers)),
+            (_, _, Some(_)) => Err(ModelError::Unsupported(
+                "the pipeline has a post-processor, load it with Pipeline::load".to_string(),
+            )),
         }
     }
 
-    pub fn train(&mut self, training_set: &Vec<Vec<f32>>, output_set: &Vec<Vec<f32>>) {
+    /// Writes the network as an ONNX model, see `serialization::onnx`.
+    pub fn export_onnx<P: AsRef<Path>>(&self, path: P) -> Result<(), ModelError> {
+        fs::write(path, onnx::to_bytes(&self.to_record()?)?)?;
+        Ok(())
+    }
+
+    /// The architecture and weights as pretty printed JSON, see
+    /// `serialization::json::to_string` for weights JSON can't hold.
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> Result<String, ModelError> {
+        crate::serialization::json::to_string(&self.to_record()?)
+    }
+
+    #[cfg(feature = "json")]
+    pub fn from_json(json: &str) -> Result<Self, ModelError> {
+        Self::from_json_with_registry(json, &Registry::default())
+    }
+
+    #[cfg(feature = "json")]
+    pub fn from_json_with_registry(json: &str, registry: &Registry) -> Result<Self, ModelError> {
+        Self::from_record(&crate::serialization::json::from_str(json)?, registry)
+    }
+
+    /// One pass over the samples, updating the weights after each of them,
+    /// and returns the mean loss. Panics if there aren't as many targets as
+    /// inputs. `fit` covers batching, shuffling and
+    /// reporting.
+    pub fn train(&mut self, training_set: &[Vec<f32>], output_set: &[Vec<f32>]) -> f32 {
+        assert_eq!(
+            training_set.len(),
+            output_set.len(),
+            "every input needs exactly one target"
+        );
+        let mut total = 0.;
         for (training, output) in training_set.iter().zip(output_set) {
-            let pred_output = self.forward(&training);
-
-            let cost: f32 = pred_output
-                .iter()
-                .zip(output)
-                .map(|(p, t)| (t - p).powi(2))
-                .sum();
-            println!("--------------------------------------");
-            println!("Cost: {}", cost);
-            println!("Real: {:?}", output);
-            println!("Pred: {:?}", pred_output);
-            println!(
-                "Last weights: {:?}",
-                self.layers[self.layers.len() - 1].weights
-            );
-            let grad = pred_output
-                .iter()
-                .zip(output)
-                .map(|(p, t)| 2. * (t - p))
-                .collect();
-            // println!("Grad: {:?}", grad);
-            self.backward(&grad);
+            self.forward(training);
+            total += self.backwards(output);
+        }
+        total / training_set.len().max(1) as f32
+    }
+}
+
+// The model of a record written by `save_with_preprocessors` or by
+// `Pipeline::save`.
+fn model_of(record: &LayerRecord) -> Result<&LayerRecord, ModelError> {
+    record
+        .layers
+        .first()
+        .ok_or_else(|| ModelError::Corrupt(format!("{} has no network", record.kind)))
+}
+
+fn compute_metrics(
+    metrics: &[Box<dyn Metric>],
+    predictions: &[Vec<f32>],
+    targets: &[Vec<f32>],
+) -> Vec<(String, f32)> {
+    metrics
+        .iter()
+        .map(|metric| {
+            (
+                metric.name().to_string(),
+                metric.compute(predictions, targets),
+            )
+        })
+        .collect()
+}
+
+impl From<Sequential> for Rann {
+    fn from(network: Sequential) -> Self {
+        Self {
+            network,
+            loss: Box::new(MeanSquaredError::new()),
+            optimizer: Box::new(Sgd::new(0.1)),
+            rng: ChaCha8Rng::from_rng(rand::thread_rng()).unwrap(),
+            output: vec![],
         }
     }
 }
@@ -71,29 +455,361 @@ impl Rann {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::testing::temp;
     use rand::Rng;
 
     #[test]
     fn rann_dims() {
         let output_size = 1;
-        let mut rann = Rann::new(&vec![5, 4, 3, 7, 9, output_size]);
+        let mut rann = Rann::new(&[5, 4, 3, 7, 9, output_size]);
         let input = vec![-1., 3., 5., 3., -6.];
         let output = rann.forward(&input);
         println!("Output: {:?}", output);
-        rann.backward(&vec![1.]);
+        rann.backward(&[1.]);
         assert_eq!(output.len(), output_size);
     }
 
+    #[test]
+    fn rann_with_activations() {
+        use crate::activations::Activation::*;
+
+        let mut rann = Rann::with_activations(&[(5, Relu), (4, Relu), (3, Softmax)]);
+        let output = rann.forward(&[-1., 3., 5., 3., -6.]);
+        assert_eq!(output.len(), 3);
+        assert!((output.iter().sum::<f32>() - 1.).abs() < 1e-5);
+
+        let mut rann = Rann::with_activations(&[(2, Linear), (1, Linear)]);
+        let output = rann.forward(&[100., 100.]);
+        assert!(output[0] > 1.);
+    }
+
+    #[test]
+    fn rann_loss_decreases() {
+        use crate::activations::Activation::*;
+        use crate::losses::Huber;
+
+        let mut rann = Rann::with_activations(&[(3, Linear), (4, Tanh), (1, Linear)])
+            .with_loss(Huber::new(1.));
+        let input = [0.5, -0.2, 0.1];
+        rann.forward(&input);
+        let first = rann.backwards(&[2.5]);
+        let mut last = first;
+        for _ in 0..200 {
+            rann.forward(&input);
+            last = rann.backwards(&[2.5]);
+        }
+        assert!(last < first);
+    }
+
+    #[test]
+    fn rann_with_optimizer() {
+        use crate::optimizers::Adam;
+
+        let mut rann = Rann::new(&[2, 3, 1]).with_optimizer(Adam::new(0.05));
+        let input = [0.4, 0.9];
+        rann.forward(&input);
+        let first = rann.backwards(&[0.1]);
+        let mut last = first;
+        for _ in 0..100 {
+            rann.forward(&input);
+            last = rann.backwards(&[0.1]);
+        }
+        assert!(last < first);
+    }
+
+    #[test]
+    fn rann_batch_uses_mean_gradient() {
+        use crate::activations::Activation::*;
+
+        let inputs = vec![vec![0.5, -0.2], vec![-0.3, 0.8], vec![0.1, 0.1]];
+        let targets = vec![vec![1.], vec![-1.], vec![0.5]];
+        let mut rann = Rann::with_activations(&[(2, Linear), (1, Linear)]);
+
+        for (input, target) in inputs.iter().zip(&targets) {
+            rann.forward(input);
+            rann.accumulate(target);
+        }
+        let parameters = rann.network.parameters();
+        let expected: Vec<f32> = parameters[0]
+            .values
+            .iter()
+            .zip(parameters[0].gradients.iter())
+            .map(|(value, gradient)| value - 0.1 * gradient / 3.)
+            .collect();
+        rann.network.zero_gradients();
+
+        rann.train_batch(&inputs, &targets);
+        let weights = rann.network.parameters()[0].values.to_vec();
+        for (w, e) in weights.iter().zip(expected) {
+            assert!((w - e).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn rann_trains_in_batches() {
+        use crate::activations::Activation::*;
+
+        let inputs: Vec<Vec<f32>> = (0..128).map(|i| vec![i as f32 / 128.]).collect();
+        let targets: Vec<Vec<f32>> = inputs.iter().map(|x| vec![3. * x[0] + 0.2]).collect();
+        let mut rann = Rann::with_activations(&[(1, Linear), (1, Linear)]);
+        let mut first = None;
+        let mut last = 0.;
+        for _ in 0..200 {
+            last = 0.;
+            for (batch, batch_targets) in inputs.chunks(64).zip(targets.chunks(64)) {
+                last += rann.train_batch(batch, batch_targets);
+            }
+            first.get_or_insert(last);
+        }
+        assert!(last < first.unwrap() / 10.);
+    }
+
+    #[test]
+    fn rann_seed_reproducible() {
+        use crate::activations::Activation::*;
+        use crate::optimizers::Adam;
+
+        let build = |seed| {
+            Rann::with_activations(&[(3, Linear), (5, Relu), (2, Softmax)])
+                .with_optimizer(Adam::new(0.01))
+                .with_seed(seed)
+        };
+        let mut a = build(7);
+        let mut b = build(7);
+        let mut c = build(8);
+        let input = [0.1, -0.4, 0.9];
+        assert_eq!(a.forward(&input), b.forward(&input));
+        assert_ne!(a.forward(&input), c.forward(&input));
+
+        for _ in 0..20 {
+            a.forward(&input);
+            b.forward(&input);
+            assert_eq!(a.backwards(&[0., 1.]), b.backwards(&[0., 1.]));
+        }
+        assert_eq!(a.forward(&input), b.forward(&input));
+    }
+
+    #[test]
+    fn rann_save_load() {
+        use crate::activations::Activation::*;
+
+        let path = temp("save_load.rann");
+        let mut rann = Rann::with_activations(&[(3, Linear), (4, Tanh), (3, Relu), (2, Softmax)]);
+        for _ in 0..10 {
+            rann.forward(&[0.3, 0.1, -0.5]);
+            rann.backwards(&[1., 0.]);
+        }
+        rann.save(&path).unwrap();
+        let mut loaded = Rann::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        for input in [[0.3, 0.1, -0.5], [1., -2., 0.7]].iter() {
+            assert_eq!(loaded.forward(input), rann.forward(input));
+        }
+    }
+
+    #[test]
+    fn rann_save_with_preprocessors() {
+        use crate::activations::Activation::*;
+        use crate::preprocessing::{OneHotEncoder, StandardScaler};
+
+        let path = temp("save_with_preprocessors.rann");
+        let rows = vec![vec![0., 2.], vec![1., 4.], vec![1., 9.]];
+        let mut encoder = OneHotEncoder::new().with_columns(&[0]);
+        let encoded = encoder.fit_transform(&rows);
+        let mut scaler = StandardScaler::new();
+        scaler.fit(&encoded);
+        let mut rann = Rann::with_activations(&[(3, Linear), (2, Sigmoid)]).with_seed(4);
+        rann.save_with_preprocessors(&path, &[&encoder, &scaler])
+            .unwrap();
+
+        let (mut loaded, preprocessors) = Rann::load_with_preprocessors(&path).unwrap();
+        let mut plain = Rann::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(preprocessors.len(), 2);
+        for row in rows.iter() {
+            let input = preprocessors.iter().fold(row.clone(), |row, preprocessor| {
+                preprocessor.transform(&row)
+            });
+            let expected = rann.forward(&scaler.transform(&encoder.transform(row)));
+            assert_eq!(loaded.forward(&input), expected);
+            assert_eq!(plain.forward(&input), expected);
+        }
+
+        rann.save(&path).unwrap();
+        let (_, preprocessors) = Rann::load_with_preprocessors(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(preprocessors.is_empty());
+    }
+
+    #[test]
+    fn rann_load_errors() {
+        use crate::activations::{Activation::*, ActivationFunc};
+
+        struct Swish;
+        impl ActivationFunc for Swish {
+            fn compute(&self, val: f32) -> f32 {
+                val / (1. + (-val).exp())
+            }
+            fn compute_derivative(&self, _val: f32) -> f32 {
+                1.
+            }
+            fn name(&self) -> &str {
+                "swish"
+            }
+        }
+
+        let path = temp("load_errors.rann");
+        let rann = Rann::from(Sequential::new().add(LayerDense::new(2, 2, Box::new(Swish))));
+        rann.save(&path).unwrap();
+        match Rann::load(&path) {
+            Err(ModelError::UnknownActivation(name)) => assert_eq!(name, "swish"),
+            _ => panic!("expected an unknown activation"),
+        }
+        let mut registry = Registry::default();
+        registry.register_activation("swish", || Box::new(Swish));
+        assert!(Rann::load_with_registry(&path, &registry).is_ok());
+
+        let scaler = crate::preprocessing::StandardScaler::new();
+        rann.save_with_preprocessors(&path, &[&scaler]).unwrap();
+        assert!(Rann::load_with_preprocessors(&path).is_err());
+        let (_, preprocessors) =
+            Rann::load_with_preprocessors_and_registry(&path, &registry).unwrap();
+        assert_eq!(preprocessors.len(), 1);
+
+        fs::write(&path, b"not a model").unwrap();
+        match Rann::load(&path) {
+            Err(ModelError::InvalidFormat(_)) => {}
+            _ => panic!("expected an invalid format"),
+        }
+
+        Rann::with_activations(&[(2, Linear), (3, Sigmoid)])
+            .save(&path)
+            .unwrap();
+        let mut bytes = fs::read(&path).unwrap();
+        let last = bytes.len() - 8;
+        bytes[last] ^= 1;
+        fs::write(&path, bytes).unwrap();
+        match Rann::load(&path) {
+            Err(ModelError::Corrupt(_)) => {}
+            _ => panic!("expected a corrupt model"),
+        }
+        fs::remove_file(&path).unwrap();
+
+        assert!(Rann::load(&path).is_err());
+
+        let mut record = Rann::new(&[2, 2]).to_record().unwrap();
+        for _ in 0..2 {
+            let mut outer = LayerRecord::new("pipeline", 0, 0);
+            outer.layers.push(record);
+            outer.layers.push(LayerRecord::new("transformers", 0, 0));
+            record = outer;
+        }
+        match Rann::from_record(&record, &Registry::default()) {
+            Err(ModelError::Corrupt(_)) => {}
+            _ => panic!("expected a corrupt model"),
+        }
+        assert!(Rann::from_record(&record.layers[0], &Registry::default()).is_ok());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn rann_json() {
+        use crate::activations::Activation::*;
+
+        let mut rann = Rann::with_activations(&[(3, Linear), (4, Relu), (2, Softmax)]);
+        let json = rann.to_json().unwrap();
+        let mut loaded = Rann::from_json(&json).unwrap();
+        assert_eq!(loaded.to_json().unwrap(), json);
+        assert_eq!(
+            loaded.forward(&[0.2, 0.4, -1.]),
+            rann.forward(&[0.2, 0.4, -1.])
+        );
+    }
+
+    fn circles(count: usize) -> (Vec<Vec<f32>>, Vec<Vec<f32>>) {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let inputs: Vec<Vec<f32>> = (0..count)
+            .map(|_| vec![rng.gen::<f32>() * 2. - 1., rng.gen::<f32>() * 2. - 1.])
+            .collect();
+        let targets = inputs
+            .iter()
+            .map(|x| match x[0] * x[0] + x[1] * x[1] < 0.4 {
+                true => vec![1., 0.],
+                false => vec![0., 1.],
+            })
+            .collect();
+        (inputs, targets)
+    }
+
+    #[test]
+    fn rann_fit() {
+        use crate::activations::Activation::*;
+        use crate::losses::CategoricalCrossEntropy;
+        use crate::metrics::Accuracy;
+        use crate::optimizers::Adam;
+
+        let dataset = circles(400);
+        let mut rann = Rann::with_activations(&[(2, Linear), (8, Tanh), (2, Softmax)])
+            .with_loss(CategoricalCrossEntropy::new())
+            .with_optimizer(Adam::new(0.02))
+            .with_seed(3);
+        let history = rann.fit(
+            &dataset,
+            FitConfig {
+                epochs: 30,
+                batch_size: 16,
+                validation: Validation::Split(0.25),
+                metrics: vec![Box::new(Accuracy::new())],
+                ..FitConfig::default()
+            },
+        );
+
+        assert_eq!(history.epochs.len(), 30);
+        let loss = history.loss();
+        let val_loss = history.val_loss();
+        assert_eq!(val_loss.len(), 30);
+        assert!(loss[29] < loss[0] / 2.);
+        assert!(val_loss[29] < val_loss[0]);
+        assert!(history.val_metric("accuracy")[29] > 0.85);
+    }
+
+    #[test]
+    fn rann_fit_reproducible() {
+        use crate::activations::Activation::*;
+
+        let dataset = circles(64);
+        let validation = circles(16);
+        let fit = || {
+            let mut rann =
+                Rann::with_activations(&[(2, Linear), (4, Relu), (2, Sigmoid)]).with_seed(5);
+            rann.fit(
+                &dataset,
+                FitConfig {
+                    epochs: 3,
+                    batch_size: 8,
+                    validation: Validation::Data(&validation),
+                    ..FitConfig::default()
+                },
+            )
+        };
+        let history = fit();
+        assert_eq!(history, fit());
+        assert!(history.epochs[0].validation.is_some());
+        assert!(history.metric("accuracy").is_empty());
+    }
+
     #[test]
     fn train_rann() {
-        let mut rnd = rand::thread_rng();
-        let training = vec![0; 1000]
+        let mut rnd = ChaCha8Rng::seed_from_u64(0);
+        let training: Vec<Vec<f32>> = vec![0; 1000]
             .iter()
             .map(|_| vec![rnd.gen(), rnd.gen(), rnd.gen(), rnd.gen(), rnd.gen()])
             .collect();
         let output = vec![vec![12.47]; 1000];
-        let mut rann = Rann::new(&vec![5, 4, 2, 1]);
-        rann.train(&training, &output);
+        let mut rann = Rann::new(&[5, 4, 2, 1]).with_seed(0);
+        let first = rann.train(&training, &output);
+        assert!(rann.train(&training, &output) < first);
         let pred_output = rann.forward(&training[0]);
         println!("Pred output: {:?}", pred_output);
         assert!(pred_output[0] - output[0][0] < 0.1 || output[0][0] - pred_output[0] < 0.1);
@@ -0,0 +1,440 @@
+use crate::callbacks::{Best, Mode, Monitor};
+use crate::training::EpochLog;
+use std::f32::consts::PI;
+
+/// How far training has come when a learning rate is asked for. Everything
+/// counts from zero.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    pub epoch: usize,
+    /// The batch within the epoch.
+    pub batch: usize,
+    pub batches_per_epoch: usize,
+}
+
+impl Progress {
+    /// Batches trained since training started.
+    pub fn step(&self) -> usize {
+        self.epoch * self.batches_per_epoch + self.batch
+    }
+
+    /// Epochs trained so far, counting the finished part of the current one.
+    pub fn epochs(&self) -> f32 {
+        self.epoch as f32 + self.batch as f32 / self.batches_per_epoch.max(1) as f32
+    }
+}
+
+/// Sets the learning rate of the optimizer before every batch of `fit`.
+pub trait LrScheduler {
+    /// The rate for the next batch. `base` is the rate the optimizer had
+    /// when training started.
+    fn learning_rate(&mut self, base: f32, progress: Progress) -> f32;
+
+    /// Called after every epoch has been validated.
+    fn on_epoch_end(&mut self, _log: &EpochLog) {}
+}
+
+/// Keeps the base rate. Mostly useful inside `LinearWarmup`.
+#[derive(Default)]
+pub struct Constant {}
+
+impl Constant {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl LrScheduler for Constant {
+    fn learning_rate(&mut self, base: f32, _progress: Progress) -> f32 {
+        base
+    }
+}
+
+/// Multiplies the rate by `gamma` every `step_size` epochs.
+pub struct StepDecay {
+    step_size: usize,
+    gamma: f32,
+}
+
+impl StepDecay {
+    pub fn new(step_size: usize, gamma: f32) -> Self {
+        Self {
+            step_size: step_size.max(1),
+            gamma,
+        }
+    }
+}
+
+impl LrScheduler for StepDecay {
+    fn learning_rate(&mut self, base: f32, progress: Progress) -> f32 {
+        base * self.gamma.powi((progress.epoch / self.step_size) as i32)
+    }
+}
+
+/// Multiplies the rate by `gamma` every epoch.
+pub struct ExponentialDecay {
+    gamma: f32,
+}
+
+impl ExponentialDecay {
+    pub fn new(gamma: f32) -> Self {
+        Self { gamma }
+    }
+}
+
+impl LrScheduler for ExponentialDecay {
+    fn learning_rate(&mut self, base: f32, progress: Progress) -> f32 {
+        base * self.gamma.powi(progress.epoch as i32)
+    }
+}
+
+// Goes from `from` to `to` along half a cosine as `fraction` goes from 0 to 1.
+fn cosine(from: f32, to: f32, fraction: f32) -> f32 {
+    to + (from - to) * (1. + (PI * fraction).cos()) / 2.
+}
+
+/// Follows half a cosine from the base rate down to `min_rate` over `period`
+/// epochs, then stays at `min_rate`. With warm restarts the rate jumps back
+/// to the base and the next period is `multiplier` times longer (SGDR).
+pub struct CosineAnnealing {
+    period: f32,
+    min_rate: f32,
+    multiplier: Option<f32>,
+}
+
+impl CosineAnnealing {
+    pub fn new(period: usize, min_rate: f32) -> Self {
+        Self {
+            period: period.max(1) as f32,
+            min_rate,
+            multiplier: None,
+        }
+    }
+
+    pub fn with_warm_restarts(mut self, multiplier: f32) -> Self {
+        self.multiplier = Some(multiplier.max(1.));
+        self
+    }
+}
+
+impl LrScheduler for CosineAnnealing {
+    fn learning_rate(&mut self, base: f32, progress: Progress) -> f32 {
+        let mut t = progress.epochs();
+        let mut period = self.period;
+        match self.multiplier {
+            None if t >= period => return self.min_rate,
+            None => {}
+            Some(multiplier) => {
+                while t >= period {
+                    t -= period;
+                    period *= multiplier;
+                }
+            }
+        }
+        cosine(base, self.min_rate, t / period)
+    }
+}
+
+/// The one-cycle policy: over the first part of `total_steps` batches the
+/// rate rises from `max_rate / 25` to `max_rate`, then it anneals to a
+/// ten-thousandth of where it started. The base rate is not used.
+pub struct OneCycle {
+    max_rate: f32,
+    total_steps: usize,
+    warmup_fraction: f32,
+}
+
+impl OneCycle {
+    pub fn new(max_rate: f32, total_steps: usize) -> Self {
+        Self {
+            max_rate,
+            total_steps: total_steps.max(1),
+            warmup_fraction: 0.3,
+        }
+    }
+
+    /// Share of `total_steps` spent increasing the rate, 0.3 by default.
+    pub fn with_warmup_fraction(mut self, fraction: f32) -> Self {
+        self.warmup_fraction = fraction.clamp(0., 1.);
+        self
+    }
+}
+
+impl LrScheduler for OneCycle {
+    fn learning_rate(&mut self, _base: f32, progress: Progress) -> f32 {
+        let initial = self.max_rate / 25.;
+        let last = initial / 1e4;
+        let step = progress.step() as f32;
+        let warmup = self.total_steps as f32 * self.warmup_fraction;
+        if step < warmup {
+            cosine(initial, self.max_rate, step / warmup)
+        } else {
+            let remaining = (self.total_steps as f32 - warmup).max(1.);
+            cosine(self.max_rate, last, ((step - warmup) / remaining).min(1.))
+        }
+    }
+}
+
+/// Moves the rate linearly between `min_rate` and `max_rate` and back,
+/// taking `step_size` batches each way. With `halving` every cycle only
+/// reaches half as far above `min_rate` as the previous one.
+pub struct Cyclic {
+    min_rate: f32,
+    max_rate: f32,
+    step_size: usize,
+    halving: bool,
+}
+
+impl Cyclic {
+    pub fn new(min_rate: f32, max_rate: f32, step_size: usize) -> Self {
+        Self {
+            min_rate,
+            max_rate,
+            step_size: step_size.max(1),
+            halving: false,
+        }
+    }
+
+    pub fn halving(mut self) -> Self {
+        self.halving = true;
+        self
+    }
+}
+
+impl LrScheduler for Cyclic {
+    fn learning_rate(&mut self, _base: f32, progress: Progress) -> f32 {
+        let step = progress.step();
+        let cycle = step / (2 * self.step_size);
+        let position = (step % (2 * self.step_size)) as f32 / self.step_size as f32;
+        let height = 1. - (position - 1.).abs();
+        let scale = match self.halving {
+            true => 0.5_f32.powi(cycle as i32),
+            false => 1.,
+        };
+        self.min_rate + (self.max_rate - self.min_rate) * height * scale
+    }
+}
+
+/// Scales the rate of `scheduler` up linearly over the first
+/// `warmup_steps` batches.
+pub struct LinearWarmup<S: LrScheduler> {
+    warmup_steps: usize,
+    scheduler: S,
+}
+
+impl<S: LrScheduler> LinearWarmup<S> {
+    pub fn new(warmup_steps: usize, scheduler: S) -> Self {
+        Self {
+            warmup_steps,
+            scheduler,
+        }
+    }
+}
+
+impl<S: LrScheduler> LrScheduler for LinearWarmup<S> {
+    fn learning_rate(&mut self, base: f32, progress: Progress) -> f32 {
+        let rate = self.scheduler.learning_rate(base, progress);
+        let step = progress.step() + 1;
+        if step < self.warmup_steps {
+            rate * step as f32 / self.warmup_steps as f32
+        } else {
+            rate
+        }
+    }
+
+    fn on_epoch_end(&mut self, log: &EpochLog) {
+        self.scheduler.on_epoch_end(log);
+    }
+}
+
+/// Multiplies the rate by `factor` whenever the monitored value, the
+/// validation loss unless changed with `monitor`, hasn't improved for
+/// `patience` epochs. Epochs without the monitored value are not counted.
+pub struct ReduceLrOnPlateau {
+    best: Best,
+    factor: f32,
+    patience: usize,
+    min_rate: f32,
+    wait: usize,
+    scale: f32,
+}
+
+impl ReduceLrOnPlateau {
+    pub fn new(factor: f32, patience: usize) -> Self {
+        Self {
+            best: Best::new(Monitor::ValLoss, Mode::Min),
+            factor,
+            patience,
+            min_rate: 0.,
+            wait: 0,
+            scale: 1.,
+        }
+    }
+
+    pub fn monitor(mut self, monitor: Monitor, mode: Mode) -> Self {
+        self.best.set_monitor(monitor, mode);
+        self
+    }
+
+    pub fn with_min_delta(mut self, min_delta: f32) -> Self {
+        self.best.min_delta = min_delta.abs();
+        self
+    }
+
+    /// The rate is never reduced below `min_rate`.
+    pub fn with_min_rate(mut self, min_rate: f32) -> Self {
+        self.min_rate = min_rate;
+        self
+    }
+}
+
+impl LrScheduler for ReduceLrOnPlateau {
+    fn learning_rate(&mut self, base: f32, _progress: Progress) -> f32 {
+        (base * self.scale).max(self.min_rate)
+    }
+
+    fn on_epoch_end(&mut self, log: &EpochLog) {
+        match self.best.update(log) {
+            None => return,
+            Some(true) => {
+                self.wait = 0;
+                return;
+            }
+            Some(false) => {}
+        }
+        self.wait += 1;
+        if self.wait >= self.patience {
+            self.scale *= self.factor;
+            self.wait = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::close;
+    use crate::training::Scores;
+
+    fn at(epoch: usize, batch: usize) -> Progress {
+        Progress {
+            epoch,
+            batch,
+            batches_per_epoch: 10,
+        }
+    }
+
+    #[test]
+    fn decays() {
+        let mut step = StepDecay::new(3, 0.5);
+        assert!(close(step.learning_rate(0.1, at(2, 9)), 0.1));
+        assert!(close(step.learning_rate(0.1, at(3, 0)), 0.05));
+        assert!(close(step.learning_rate(0.1, at(7, 0)), 0.025));
+
+        let mut exponential = ExponentialDecay::new(0.9);
+        assert!(close(exponential.learning_rate(1., at(0, 5)), 1.));
+        assert!(close(exponential.learning_rate(1., at(2, 0)), 0.81));
+    }
+
+    #[test]
+    fn cosine_annealing() {
+        let mut cosine = CosineAnnealing::new(2, 0.);
+        assert!(close(cosine.learning_rate(1., at(0, 0)), 1.));
+        assert!(close(cosine.learning_rate(1., at(1, 0)), 0.5));
+        assert!(close(cosine.learning_rate(1., at(5, 0)), 0.));
+
+        let mut restarts = CosineAnnealing::new(2, 0.1).with_warm_restarts(2.);
+        assert!(close(restarts.learning_rate(1., at(2, 0)), 1.));
+        assert!(close(restarts.learning_rate(1., at(4, 0)), 0.55));
+        assert!(close(restarts.learning_rate(1., at(6, 0)), 1.));
+    }
+
+    #[test]
+    fn cycles() {
+        let mut one_cycle = OneCycle::new(1., 100);
+        assert!(close(one_cycle.learning_rate(0.5, at(0, 0)), 0.04));
+        assert!(close(one_cycle.learning_rate(0.5, at(3, 0)), 1.));
+        assert!(close(one_cycle.learning_rate(0.5, at(10, 0)), 4e-6));
+
+        let mut cyclic = Cyclic::new(0.1, 0.5, 5);
+        let rates: Vec<f32> = (0..11)
+            .map(|i| cyclic.learning_rate(1., at(0, i)))
+            .collect();
+        assert!(close(rates[0], 0.1) && close(rates[5], 0.5) && close(rates[10], 0.1));
+        assert!(close(rates[2], 0.26));
+
+        let mut halving = Cyclic::new(0., 1., 5).halving();
+        assert!(close(halving.learning_rate(1., at(1, 5)), 0.5));
+    }
+
+    #[test]
+    fn linear_warmup() {
+        let mut warmup = LinearWarmup::new(4, StepDecay::new(1, 0.5));
+        assert!(close(warmup.learning_rate(1., at(0, 0)), 0.25));
+        assert!(close(warmup.learning_rate(1., at(0, 2)), 0.75));
+        assert!(close(warmup.learning_rate(1., at(0, 3)), 1.));
+        assert!(close(warmup.learning_rate(1., at(1, 0)), 0.5));
+    }
+
+    #[test]
+    fn reduce_on_plateau() {
+        let mut plateau = ReduceLrOnPlateau::new(0.5, 2).with_min_rate(0.02);
+        let mut rates = vec![];
+        for (epoch, val_loss) in [1., 0.9, 0.95, 0.92, 0.8, 0.85, 0.9, 0.9, 0.9, 0.9]
+            .iter()
+            .enumerate()
+        {
+            plateau.on_epoch_end(&EpochLog {
+                epoch,
+                train: Scores {
+                    loss: 1.,
+                    metrics: vec![],
+                },
+                validation: Some(Scores {
+                    loss: *val_loss,
+                    metrics: vec![],
+                }),
+                learning_rate: 0.,
+            });
+            rates.push(plateau.learning_rate(0.1, at(epoch + 1, 0)));
+        }
+        assert_eq!(
+            rates,
+            vec![0.1, 0.1, 0.1, 0.05, 0.05, 0.05, 0.025, 0.025, 0.02, 0.02]
+        );
+    }
+
+    #[test]
+    fn fit_uses_scheduler() {
+        use crate::activations::Activation::*;
+        use crate::rann::Rann;
+        use crate::training::FitConfig;
+
+        let inputs = vec![vec![0.5, 0.1]; 8];
+        let targets = vec![vec![1.]; 8];
+        let mut rann = Rann::with_activations(&[(2, Linear), (1, Sigmoid)]);
+        let history = rann.fit(
+            &(inputs, targets),
+            FitConfig {
+                epochs: 4,
+                batch_size: 4,
+                scheduler: Some(Box::new(StepDecay::new(2, 0.1))),
+                ..FitConfig::default()
+            },
+        );
+        let rates = history.learning_rate();
+        assert!(close(rates[0], 0.1) && close(rates[1], 0.1));
+        assert!(close(rates[2], 0.01) && close(rates[3], 0.01));
+        assert!(close(rann.learning_rate(), 0.1));
+
+        let history = rann.fit(
+            &(vec![vec![0.5, 0.1]; 8], vec![vec![1.]; 8]),
+            FitConfig {
+                epochs: 1,
+                scheduler: Some(Box::new(StepDecay::new(2, 0.1))),
+                ..FitConfig::default()
+            },
+        );
+        assert!(close(history.learning_rate()[0], 0.1));
+    }
+}
@@ -0,0 +1,212 @@
+use crate::layers::{Layer, Parameter, Softmax};
+use crate::node::LayerSoftmax;
+use crate::serialization::{LayerRecord, ModelError, Registry};
+use rand::RngCore;
+
+/// What `Sequential::add` accepts: any `Layer`, or `Softmax`, which is sized
+/// from the outputs of the layer before it.
+pub trait IntoLayer {
+    fn into_layer(self, previous: Option<&dyn Layer>) -> Box<dyn Layer>;
+}
+
+impl<L: Layer + 'static> IntoLayer for L {
+    fn into_layer(self, _previous: Option<&dyn Layer>) -> Box<dyn Layer> {
+        Box::new(self)
+    }
+}
+
+impl IntoLayer for Softmax {
+    fn into_layer(self, previous: Option<&dyn Layer>) -> Box<dyn Layer> {
+        let previous = previous.expect("Softmax has to follow another layer");
+        Box::new(LayerSoftmax::new(previous.outputs()))
+    }
+}
+
+/// A stack of layers run one after the other. Any type implementing `Layer`
+/// can be added, including another `Sequential`.
+#[derive(Default)]
+pub struct Sequential {
+    layers: Vec<Box<dyn Layer>>,
+}
+
+impl Sequential {
+    pub fn new() -> Self {
+        Self { layers: Vec::new() }
+    }
+
+    /// Appends a layer.
+    // Named like the builders of other libraries. `Add` isn't implemented
+    // instead, since `model + layer` would read as arithmetic.
+    #[allow(clippy::should_implement_trait)]
+    pub fn add<L: IntoLayer>(mut self, layer: L) -> Self {
+        let layer = layer.into_layer(self.layers.last().map(|layer| layer.as_ref()));
+        self.layers.push(layer);
+        self
+    }
+
+    pub fn push(&mut self, layer: Box<dyn Layer>) {
+        self.layers.push(layer);
+    }
+
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Rebuilds every child of `record` through `registry`.
+    pub fn from_record(record: &LayerRecord, registry: &Registry) -> Result<Self, ModelError> {
+        let mut sequential = Self::new();
+        for (i, child) in record.layers.iter().enumerate() {
+            if i > 0 && record.layers[i - 1].outputs != child.inputs {
+                return Err(ModelError::Corrupt(format!(
+                    "layer {} expects {} inputs but the previous layer has {} outputs",
+                    i,
+                    child.inputs,
+                    record.layers[i - 1].outputs
+                )));
+            }
+            sequential.push(registry.layer(child)?);
+        }
+        Ok(sequential)
+    }
+}
+
+impl Layer for Sequential {
+    fn forward(&mut self, inputs: &[f32]) -> Vec<f32> {
+        let mut output = inputs.to_vec();
+        for layer in self.layers.iter_mut() {
+            output = layer.forward(&output);
+        }
+        output
+    }
+
+    fn backwards(&mut self, derivatives: &[f32]) -> Vec<f32> {
+        let mut derivatives = derivatives.to_vec();
+        for layer in self.layers.iter_mut().rev() {
+            derivatives = layer.backwards(&derivatives);
+        }
+        derivatives
+    }
+
+    fn outputs(&self) -> usize {
+        self.layers.last().map_or(0, |layer| layer.outputs())
+    }
+
+    fn initialize(&mut self, rng: &mut dyn RngCore) {
+        for layer in self.layers.iter_mut() {
+            layer.initialize(rng);
+        }
+    }
+
+    fn parameters(&mut self) -> Vec<Parameter<'_>> {
+        self.layers
+            .iter_mut()
+            .flat_map(|layer| layer.parameters())
+            .collect()
+    }
+
+    fn sublayers(&mut self) -> Vec<&mut dyn Layer> {
+        self.layers
+            .iter_mut()
+            .map(|layer| layer.as_mut() as &mut dyn Layer)
+            .collect()
+    }
+
+    fn to_record(&self) -> Result<LayerRecord, ModelError> {
+        let layers = self
+            .layers
+            .iter()
+            .map(|layer| layer.to_record())
+            .collect::<Result<Vec<_>, _>>()?;
+        let inputs = layers.first().map_or(0, |layer| layer.inputs);
+        let outputs = layers.last().map_or(0, |layer| layer.outputs);
+        let mut record = LayerRecord::new("sequential", inputs, outputs);
+        record.layers = layers;
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::activations::{Relu, Sigmoid};
+    use crate::layers::dense::LayerDense;
+    use crate::node::{self, LayerSoftmax};
+    use std::rc::Rc;
+
+    #[test]
+    fn sequential_mixed_layers() {
+        let mut model = Sequential::new()
+            .add(LayerDense::new(5, 4, Box::new(Relu::new())))
+            .add(node::LayerDense::new(4, 3, Rc::new(Sigmoid::new())))
+            .add(LayerSoftmax::new(3));
+        assert_eq!(model.len(), 3);
+
+        let output = model.forward(&[1.0, -3.0, 3.0, 1.0, 6.0]);
+        assert_eq!(output.len(), 3);
+        assert!((output.iter().sum::<f32>() - 1.).abs() < 1e-5);
+
+        let input_grads = model.backwards(&[0.1, -0.2, 0.1]);
+        assert_eq!(input_grads.len(), 5);
+    }
+
+    #[test]
+    fn sequential_builder() {
+        use crate::activations::Activation;
+        use crate::layers::Dense;
+
+        let mut model = Sequential::new()
+            .add(Dense::new(784, 32, Relu))
+            .add(Dense::new(32, 10, Activation::Linear))
+            .add(Softmax);
+        assert_eq!(model.len(), 3);
+        let output = model.forward(&[0.5; 784]);
+        assert_eq!(output.len(), 10);
+        assert!((output.iter().sum::<f32>() - 1.).abs() < 1e-5);
+        let record = model.to_record().unwrap();
+        assert_eq!(record.layers[2].kind, "softmax");
+        assert_eq!(record.layers[2].inputs, 10);
+    }
+
+    #[test]
+    fn softmax_after_unsaved_layer() {
+        // a layer without to_record still tells its width
+        struct Double(usize);
+        impl Layer for Double {
+            fn forward(&mut self, inputs: &[f32]) -> Vec<f32> {
+                inputs.iter().map(|x| 2. * x).collect()
+            }
+            fn backwards(&mut self, derivatives: &[f32]) -> Vec<f32> {
+                derivatives.iter().map(|d| 2. * d).collect()
+            }
+            fn outputs(&self) -> usize {
+                self.0
+            }
+        }
+
+        let mut model = Sequential::new().add(Double(3)).add(Softmax);
+        assert_eq!(model.outputs(), 3);
+        let output = model.forward(&[0.1, 0.2, 0.3]);
+        assert!((output.iter().sum::<f32>() - 1.).abs() < 1e-5);
+    }
+
+    #[test]
+    #[should_panic(expected = "Softmax has to follow")]
+    fn softmax_needs_a_previous_layer() {
+        Sequential::new().add(Softmax);
+    }
+
+    #[test]
+    fn sequential_nested() {
+        let inner = Sequential::new().add(LayerDense::new(3, 2, Box::new(Sigmoid::new())));
+        let mut model =
+            Sequential::new()
+                .add(inner)
+                .add(LayerDense::new(2, 1, Box::new(Sigmoid::new())));
+        let output = model.forward(&[1., 2., 3.]);
+        assert_eq!(output.len(), 1);
+    }
+}
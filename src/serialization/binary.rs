@@ -0,0 +1,278 @@
+//! Compact binary model format.
+//!
+//! A file starts with the magic bytes `RANN`, a little-endian `u32` format
+//! version and the `u64` length of the payload. The payload is the encoded
+//! `LayerRecord` tree and is followed by its CRC-32.
+
+use crate::serialization::{LayerRecord, ModelError};
+use crate::tensor::Tensor;
+
+pub const MAGIC: &[u8; 4] = b"RANN";
+pub const VERSION: u32 = 1;
+/// How deeply records may nest, far beyond any real model, so that a
+/// crafted file can't overflow the stack.
+pub const MAX_DEPTH: usize = 64;
+
+pub fn encode(record: &LayerRecord) -> Vec<u8> {
+    let mut payload = vec![];
+    write_record(&mut payload, record);
+
+    let mut bytes = Vec::with_capacity(payload.len() + 20);
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    bytes.extend_from_slice(&crc32(&payload).to_le_bytes());
+    bytes
+}
+
+pub fn decode(bytes: &[u8]) -> Result<LayerRecord, ModelError> {
+    if bytes.len() < 4 || &bytes[..4] != MAGIC {
+        return Err(ModelError::InvalidFormat("missing RANN header".to_string()));
+    }
+    let mut reader = Reader { bytes, position: 4 };
+    let version = reader.u32()?;
+    if version != VERSION {
+        return Err(ModelError::UnsupportedVersion(version));
+    }
+    let length = reader.u64()? as usize;
+    let payload = reader.take(length)?;
+    let checksum = reader.u32()?;
+    if checksum != crc32(payload) {
+        return Err(ModelError::Corrupt("checksum mismatch".to_string()));
+    }
+    if reader.position != bytes.len() {
+        return Err(ModelError::Corrupt("trailing data".to_string()));
+    }
+
+    let mut reader = Reader {
+        bytes: payload,
+        position: 0,
+    };
+    let record = read_record(&mut reader, 0)?;
+    if reader.position != payload.len() {
+        return Err(ModelError::Corrupt("trailing data in payload".to_string()));
+    }
+    Ok(record)
+}
+
+fn write_u32(out: &mut Vec<u8>, value: usize) {
+    out.extend_from_slice(&(value as u32).to_le_bytes());
+}
+
+fn write_str(out: &mut Vec<u8>, value: &str) {
+    write_u32(out, value.len());
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn write_record(out: &mut Vec<u8>, record: &LayerRecord) {
+    write_str(out, &record.kind);
+    write_u32(out, record.inputs);
+    write_u32(out, record.outputs);
+    match &record.activation {
+        Some(name) => {
+            out.push(1);
+            write_str(out, name);
+        }
+        None => out.push(0),
+    }
+    write_u32(out, record.tensors.len());
+    for (name, tensor) in record.tensors.iter() {
+        write_str(out, name);
+        write_u32(out, tensor.rank());
+        for dim in tensor.shape() {
+            write_u32(out, *dim);
+        }
+        for value in tensor.data() {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    write_u32(out, record.layers.len());
+    for layer in record.layers.iter() {
+        write_record(out, layer);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], ModelError> {
+        if self.bytes.len() - self.position < count {
+            return Err(ModelError::Corrupt("unexpected end of data".to_string()));
+        }
+        let bytes = &self.bytes[self.position..self.position + count];
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, ModelError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, ModelError> {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn u64(&mut self) -> Result<u64, ModelError> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    fn usize(&mut self) -> Result<usize, ModelError> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn f32(&mut self) -> Result<f32, ModelError> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    fn string(&mut self) -> Result<String, ModelError> {
+        let length = self.usize()?;
+        String::from_utf8(self.take(length)?.to_vec())
+            .map_err(|_| ModelError::Corrupt("invalid utf-8 in string".to_string()))
+    }
+}
+
+fn read_record(reader: &mut Reader, depth: usize) -> Result<LayerRecord, ModelError> {
+    if depth > MAX_DEPTH {
+        return Err(ModelError::Corrupt(format!(
+            "records nest deeper than {}",
+            MAX_DEPTH
+        )));
+    }
+    let kind = reader.string()?;
+    let mut record = LayerRecord::new(&kind, reader.usize()?, reader.usize()?);
+    record.activation = match reader.u8()? {
+        0 => None,
+        1 => Some(reader.string()?),
+        flag => return Err(ModelError::Corrupt(format!("bad activation flag {}", flag))),
+    };
+    for _ in 0..reader.usize()? {
+        let name = reader.string()?;
+        let rank = reader.usize()?;
+        let mut shape = Vec::with_capacity(rank.min(8));
+        for _ in 0..rank {
+            shape.push(reader.usize()?);
+        }
+        let size = shape
+            .iter()
+            .try_fold(1_usize, |acc, dim| acc.checked_mul(*dim))
+            .filter(|size| size.saturating_mul(4) <= reader.bytes.len() - reader.position)
+            .ok_or_else(|| ModelError::Corrupt(format!("tensor {} is too large", name)))?;
+        let mut data = Vec::with_capacity(size);
+        for _ in 0..size {
+            data.push(reader.f32()?);
+        }
+        record.tensors.push((name, Tensor::new(data, &shape)));
+    }
+    for _ in 0..reader.usize()? {
+        record.layers.push(read_record(reader, depth + 1)?);
+    }
+    Ok(record)
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record() -> LayerRecord {
+        let mut dense = LayerRecord::new("dense", 3, 2);
+        dense.activation = Some("relu".to_string());
+        dense.tensors.push((
+            "weights".to_string(),
+            Tensor::new(vec![1., 2., 3., 4., 5., 6.], &[2, 3]),
+        ));
+        let mut sequential = LayerRecord::new("sequential", 3, 2);
+        sequential.layers.push(dense);
+        sequential.layers.push(LayerRecord::new("softmax", 2, 2));
+        sequential
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn round_trip() {
+        let record = record();
+        assert_eq!(decode(&encode(&record)).unwrap(), record);
+    }
+
+    #[test]
+    fn rejects_bad_data() {
+        let bytes = encode(&record());
+
+        match decode(b"PK\x03\x04 not a model") {
+            Err(ModelError::InvalidFormat(_)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let mut future = bytes.clone();
+        future[4] = 2;
+        match decode(&future) {
+            Err(ModelError::UnsupportedVersion(2)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let mut flipped = bytes.clone();
+        flipped[30] ^= 0xff;
+        match decode(&flipped) {
+            Err(ModelError::Corrupt(_)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+
+        match decode(&bytes[..bytes.len() - 10]) {
+            Err(ModelError::Corrupt(_)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    // A file of records each holding the next, `depth` deep, with a valid
+    // checksum.
+    fn nested(depth: usize) -> Vec<u8> {
+        let mut payload = vec![];
+        for level in 0..depth {
+            write_str(&mut payload, "sequential");
+            payload.extend_from_slice(&[0; 8]);
+            payload.push(0);
+            write_u32(&mut payload, 0);
+            write_u32(&mut payload, usize::from(level + 1 < depth));
+        }
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&payload);
+        bytes.extend_from_slice(&crc32(&payload).to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn rejects_deep_nesting() {
+        assert!(decode(&nested(MAX_DEPTH + 1)).is_ok());
+        for depth in [MAX_DEPTH + 2, 1_000_000].iter() {
+            match decode(&nested(*depth)) {
+                Err(ModelError::Corrupt(_)) => {}
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! Human readable model format, mainly for diffing and for handing models to
+//! tools outside of Rust. Needs the `json` feature.
+
+use crate::serialization::{LayerRecord, ModelError};
+use crate::tensor::Tensor;
+use serde::{Deserialize, Serialize};
+
+pub const FORMAT: &str = "rann";
+pub const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct JsonModel {
+    format: String,
+    version: u32,
+    model: JsonLayer,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonLayer {
+    kind: String,
+    inputs: usize,
+    outputs: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    activation: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tensors: Vec<JsonTensor>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    layers: Vec<JsonLayer>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonTensor {
+    name: String,
+    shape: Vec<usize>,
+    data: Vec<f32>,
+}
+
+impl From<&LayerRecord> for JsonLayer {
+    fn from(record: &LayerRecord) -> Self {
+        Self {
+            kind: record.kind.clone(),
+            inputs: record.inputs,
+            outputs: record.outputs,
+            activation: record.activation.clone(),
+            tensors: record
+                .tensors
+                .iter()
+                .map(|(name, tensor)| JsonTensor {
+                    name: name.clone(),
+                    shape: tensor.shape().to_vec(),
+                    data: tensor.data().to_vec(),
+                })
+                .collect(),
+            layers: record.layers.iter().map(JsonLayer::from).collect(),
+        }
+    }
+}
+
+impl JsonLayer {
+    fn into_record(self) -> Result<LayerRecord, ModelError> {
+        let mut record = LayerRecord::new(&self.kind, self.inputs, self.outputs);
+        record.activation = self.activation;
+        for tensor in self.tensors {
+            let size = tensor
+                .shape
+                .iter()
+                .try_fold(1_usize, |acc, dim| acc.checked_mul(*dim));
+            if size != Some(tensor.data.len()) {
+                return Err(ModelError::Corrupt(format!(
+                    "{} of {} layer has {} values for shape {:?}",
+                    tensor.name,
+                    self.kind,
+                    tensor.data.len(),
+                    tensor.shape
+                )));
+            }
+            let data = Tensor::new(tensor.data, &tensor.shape);
+            record.tensors.push((tensor.name, data));
+        }
+        for layer in self.layers {
+            record.layers.push(layer.into_record()?);
+        }
+        Ok(record)
+    }
+}
+
+// JSON has no NaN or infinity; serde_json would write them as null.
+fn check_finite(record: &LayerRecord) -> Result<(), ModelError> {
+    for (name, tensor) in record.tensors.iter() {
+        if let Some(value) = tensor.data().iter().find(|value| !value.is_finite()) {
+            return Err(ModelError::Unsupported(format!(
+                "{} of {} layer holds {}, which JSON can't represent",
+                name, record.kind, value
+            )));
+        }
+    }
+    record.layers.iter().try_for_each(check_finite)
+}
+
+/// Pretty printed JSON for `record`. Weights that are NaN or infinite are
+/// `ModelError::Unsupported`, as JSON numbers can't hold them; the binary
+/// format can.
+pub fn to_string(record: &LayerRecord) -> Result<String, ModelError> {
+    check_finite(record)?;
+    let model = JsonModel {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        model: JsonLayer::from(record),
+    };
+    Ok(serde_json::to_string_pretty(&model).expect("model records always serialize"))
+}
+
+pub fn from_str(json: &str) -> Result<LayerRecord, ModelError> {
+    let model: JsonModel =
+        serde_json::from_str(json).map_err(|err| ModelError::InvalidFormat(err.to_string()))?;
+    if model.format != FORMAT {
+        return Err(ModelError::InvalidFormat(format!(
+            "format is '{}', expected '{}'",
+            model.format, FORMAT
+        )));
+    }
+    if model.version != VERSION {
+        return Err(ModelError::UnsupportedVersion(model.version));
+    }
+    model.model.into_record()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn json_round_trip() {
+        let mut dense = LayerRecord::new("dense", 2, 2);
+        dense.activation = Some("tanh".to_string());
+        dense.tensors.push((
+            "weights".to_string(),
+            Tensor::new(vec![0.1, -0.25, 3e-8, 12.5], &[2, 2]),
+        ));
+        let mut record = LayerRecord::new("sequential", 2, 2);
+        record.layers.push(dense);
+        record.layers.push(LayerRecord::new("softmax", 2, 2));
+
+        let json = to_string(&record).unwrap();
+        assert!(json.contains("\"activation\": \"tanh\""));
+        assert_eq!(from_str(&json).unwrap(), record);
+    }
+
+    #[test]
+    fn non_finite_weights() {
+        for value in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY].iter() {
+            let mut dense = LayerRecord::new("dense", 1, 1);
+            dense
+                .tensors
+                .push(("biases".to_string(), Tensor::new(vec![*value], &[1])));
+            let mut record = LayerRecord::new("sequential", 1, 1);
+            record.layers.push(dense);
+            match to_string(&record) {
+                Err(ModelError::Unsupported(msg)) => assert!(msg.contains("biases"), "{}", msg),
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn json_errors() {
+        match from_str("[1, 2]") {
+            Err(ModelError::InvalidFormat(_)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        let model = r#"{"format": "rann", "version": 9, "model": {"kind": "softmax", "inputs": 2, "outputs": 2}}"#;
+        match from_str(model) {
+            Err(ModelError::UnsupportedVersion(9)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        let model = r#"{"format": "rann", "version": 1, "model": {"kind": "dense", "inputs": 2, "outputs": 1,
+            "tensors": [{"name": "weights", "shape": [1, 2], "data": [1.0]}]}}"#;
+        match from_str(model) {
+            Err(ModelError::Corrupt(_)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        // 2^32 * 2^32 wraps to 0 in 64 bits and would match the empty data
+        let model = r#"{"format": "rann", "version": 1, "model": {"kind": "dense", "inputs": 2, "outputs": 1,
+            "tensors": [{"name": "weights", "shape": [4294967296, 4294967296], "data": []}]}}"#;
+        match from_str(model) {
+            Err(ModelError::Corrupt(_)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
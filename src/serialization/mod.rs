@@ -0,0 +1,231 @@
+pub mod binary;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod onnx;
+
+use crate::activations::{ActivationFunc, Linear, Relu, Sigmoid, Tanh};
+use crate::layers::Layer;
+use crate::preprocessing::Transformer;
+use crate::tensor::Tensor;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Format independent description of a layer: what kind it is, its sizes,
+/// its activation and its trainable tensors. Containers such as `Sequential`
+/// keep their children in `layers`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayerRecord {
+    pub kind: String,
+    pub inputs: usize,
+    pub outputs: usize,
+    pub activation: Option<String>,
+    pub tensors: Vec<(String, Tensor)>,
+    pub layers: Vec<LayerRecord>,
+}
+
+impl LayerRecord {
+    pub fn new(kind: &str, inputs: usize, outputs: usize) -> Self {
+        Self {
+            kind: kind.to_string(),
+            inputs,
+            outputs,
+            activation: None,
+            tensors: vec![],
+            layers: vec![],
+        }
+    }
+
+    pub fn tensor(&self, name: &str) -> Option<&Tensor> {
+        self.tensors.iter().find(|(n, _)| n == name).map(|(_, t)| t)
+    }
+
+    /// Like `tensor`, but the tensor must exist and have `shape`.
+    pub fn expect_tensor(&self, name: &str, shape: &[usize]) -> Result<&Tensor, ModelError> {
+        match self.tensor(name) {
+            Some(tensor) if tensor.shape() == shape => Ok(tensor),
+            Some(tensor) => Err(ModelError::Corrupt(format!(
+                "{} of {} layer has shape {:?}, expected {:?}",
+                name,
+                self.kind,
+                tensor.shape(),
+                shape
+            ))),
+            None => Err(ModelError::Corrupt(format!(
+                "{} layer is missing {}",
+                self.kind, name
+            ))),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ModelError {
+    Io(io::Error),
+    /// The data is not a saved model at all.
+    InvalidFormat(String),
+    /// The model was written by a format version this build can't read.
+    UnsupportedVersion(u32),
+    /// The data is a model but is damaged or inconsistent.
+    Corrupt(String),
+    UnknownActivation(String),
+    UnknownLayer(String),
+    UnknownTransformer(String),
+    /// The model contains something that can't be saved.
+    Unsupported(String),
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelError::Io(err) => write!(f, "io error: {}", err),
+            ModelError::InvalidFormat(msg) => write!(f, "not a model file: {}", msg),
+            ModelError::UnsupportedVersion(version) => {
+                write!(f, "unsupported model format version {}", version)
+            }
+            ModelError::Corrupt(msg) => write!(f, "corrupt model: {}", msg),
+            ModelError::UnknownActivation(name) => write!(f, "unknown activation '{}'", name),
+            ModelError::UnknownLayer(kind) => write!(f, "unknown layer kind '{}'", kind),
+            ModelError::UnknownTransformer(kind) => {
+                write!(f, "unknown transformer kind '{}'", kind)
+            }
+            ModelError::Unsupported(msg) => write!(f, "unsupported: {}", msg),
+        }
+    }
+}
+
+impl Error for ModelError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ModelError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ModelError {
+    fn from(err: io::Error) -> Self {
+        ModelError::Io(err)
+    }
+}
+
+pub type ActivationConstructor = fn() -> Box<dyn ActivationFunc>;
+pub type LayerConstructor = fn(&LayerRecord, &Registry) -> Result<Box<dyn Layer>, ModelError>;
+pub type TransformerConstructor = fn(&LayerRecord) -> Result<Box<dyn Transformer>, ModelError>;
+
+/// Maps the names found in saved models back to activations, layers and
+/// preprocessing transformers. `Registry::default()` knows everything that
+/// ships with the crate; custom ones are added with `register_activation`,
+/// `register_layer` and `register_transformer`.
+pub struct Registry {
+    activations: HashMap<String, ActivationConstructor>,
+    layers: HashMap<String, LayerConstructor>,
+    transformers: HashMap<String, TransformerConstructor>,
+}
+
+impl Registry {
+    pub fn empty() -> Self {
+        Self {
+            activations: HashMap::new(),
+            layers: HashMap::new(),
+            transformers: HashMap::new(),
+        }
+    }
+
+    pub fn register_activation(&mut self, name: &str, constructor: ActivationConstructor) {
+        self.activations.insert(name.to_string(), constructor);
+    }
+
+    pub fn register_layer(&mut self, kind: &str, constructor: LayerConstructor) {
+        self.layers.insert(kind.to_string(), constructor);
+    }
+
+    pub fn register_transformer(&mut self, kind: &str, constructor: TransformerConstructor) {
+        self.transformers.insert(kind.to_string(), constructor);
+    }
+
+    pub fn activation(&self, name: &str) -> Result<Box<dyn ActivationFunc>, ModelError> {
+        match self.activations.get(name) {
+            Some(constructor) => Ok(constructor()),
+            None => Err(ModelError::UnknownActivation(name.to_string())),
+        }
+    }
+
+    /// The activation of `record`, which must have one.
+    pub fn record_activation(
+        &self,
+        record: &LayerRecord,
+    ) -> Result<Box<dyn ActivationFunc>, ModelError> {
+        match &record.activation {
+            Some(name) => self.activation(name),
+            None => Err(ModelError::Corrupt(format!(
+                "{} layer has no activation",
+                record.kind
+            ))),
+        }
+    }
+
+    pub fn layer(&self, record: &LayerRecord) -> Result<Box<dyn Layer>, ModelError> {
+        match self.layers.get(&record.kind) {
+            Some(constructor) => constructor(record, self),
+            None => Err(ModelError::UnknownLayer(record.kind.clone())),
+        }
+    }
+
+    pub fn transformer(&self, record: &LayerRecord) -> Result<Box<dyn Transformer>, ModelError> {
+        match self.transformers.get(&record.kind) {
+            Some(constructor) => constructor(record),
+            None => Err(ModelError::UnknownTransformer(record.kind.clone())),
+        }
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        use crate::preprocessing::*;
+
+        let mut registry = Self::empty();
+        registry.register_activation("linear", || Box::new(Linear::new()));
+        registry.register_activation("relu", || Box::new(Relu::new()));
+        registry.register_activation("sigmoid", || Box::new(Sigmoid::new()));
+        registry.register_activation("tanh", || Box::new(Tanh::new()));
+
+        registry.register_layer("dense", |record, registry| {
+            Ok(Box::new(crate::layers::dense::LayerDense::from_record(
+                record, registry,
+            )?))
+        });
+        registry.register_layer("node_dense", |record, registry| {
+            Ok(Box::new(crate::node::LayerDense::from_record(
+                record, registry,
+            )?))
+        });
+        registry.register_layer("softmax", |record, _| {
+            Ok(Box::new(crate::node::LayerSoftmax::new(record.inputs)))
+        });
+        registry.register_layer("sequential", |record, registry| {
+            Ok(Box::new(crate::sequential::Sequential::from_record(
+                record, registry,
+            )?))
+        });
+
+        registry.register_transformer("standard_scaler", |record| {
+            Ok(Box::new(StandardScaler::from_record(record)?))
+        });
+        registry.register_transformer("min_max_scaler", |record| {
+            Ok(Box::new(MinMaxScaler::from_record(record)?))
+        });
+        registry.register_transformer("robust_scaler", |record| {
+            Ok(Box::new(RobustScaler::from_record(record)?))
+        });
+        registry.register_transformer("normalizer", |_| Ok(Box::new(Normalizer::new())));
+        registry.register_transformer("one_hot_encoder", |record| {
+            Ok(Box::new(OneHotEncoder::from_record(record)?))
+        });
+        registry.register_transformer("ordinal_encoder", |record| {
+            Ok(Box::new(OrdinalEncoder::from_record(record)?))
+        });
+        registry
+    }
+}
@@ -0,0 +1,395 @@
+//! ONNX export for dense networks.
+//!
+//! Dense layers become a `Gemm` (with `transB`, since weights are stored as
+//! `[outputs, inputs]`) followed by the node for their activation, and
+//! `LayerSoftmax` becomes `Softmax`. The graph takes a `[batch, inputs]`
+//! tensor named `input` and produces `output`. The protobuf is written by
+//! hand, so no ONNX or protobuf crate is needed.
+
+use crate::serialization::{LayerRecord, ModelError};
+
+pub const IR_VERSION: u64 = 7;
+pub const OPSET_VERSION: u64 = 13;
+
+// TensorProto.DataType.FLOAT and AttributeProto.AttributeType.INT
+const FLOAT: u64 = 1;
+const INT: u64 = 2;
+
+#[derive(Default)]
+struct Message {
+    bytes: Vec<u8>,
+}
+
+impl Message {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+
+    fn key(&mut self, field: u64, wire_type: u64) {
+        self.varint(field << 3 | wire_type);
+    }
+
+    fn uint(&mut self, field: u64, value: u64) -> &mut Self {
+        self.key(field, 0);
+        self.varint(value);
+        self
+    }
+
+    fn bytes(&mut self, field: u64, value: &[u8]) -> &mut Self {
+        self.key(field, 2);
+        self.varint(value.len() as u64);
+        self.bytes.extend_from_slice(value);
+        self
+    }
+
+    fn string(&mut self, field: u64, value: &str) -> &mut Self {
+        self.bytes(field, value.as_bytes())
+    }
+
+    fn message(&mut self, field: u64, value: &Message) -> &mut Self {
+        self.bytes(field, &value.bytes)
+    }
+}
+
+struct Graph {
+    nodes: Vec<Message>,
+    initializers: Vec<Message>,
+    last_output: String,
+}
+
+impl Graph {
+    fn node(&mut self, op_type: &str, extra_inputs: &[String], attributes: &[(&str, i64)]) {
+        let output = format!("{}_{}", op_type.to_lowercase(), self.nodes.len());
+        self.node_to(op_type, extra_inputs, attributes, output);
+    }
+
+    fn node_to(
+        &mut self,
+        op_type: &str,
+        extra_inputs: &[String],
+        attributes: &[(&str, i64)],
+        output: String,
+    ) {
+        let mut node = Message::default();
+        node.string(1, &self.last_output);
+        for input in extra_inputs {
+            node.string(1, input);
+        }
+        node.string(2, &output)
+            .string(3, &output)
+            .string(4, op_type);
+        for (name, value) in attributes {
+            let mut attribute = Message::default();
+            attribute
+                .string(1, name)
+                .uint(3, *value as u64)
+                .uint(20, INT);
+            node.message(5, &attribute);
+        }
+        self.nodes.push(node);
+        self.last_output = output;
+    }
+
+    fn initializer(&mut self, name: String, dims: &[usize], data: &[f32]) -> String {
+        let mut tensor = Message::default();
+        for dim in dims {
+            tensor.uint(1, *dim as u64);
+        }
+        let raw: Vec<u8> = data.iter().flat_map(|value| value.to_le_bytes()).collect();
+        tensor.uint(2, FLOAT).string(8, &name).bytes(9, &raw);
+        self.initializers.push(tensor);
+        name
+    }
+
+    fn add_layer(&mut self, record: &LayerRecord) -> Result<(), ModelError> {
+        match record.kind.as_str() {
+            "sequential" => {
+                for layer in record.layers.iter() {
+                    self.add_layer(layer)?;
+                }
+            }
+            "dense" | "node_dense" => {
+                let prefix = format!("dense_{}", self.nodes.len());
+                let weights = record.expect_tensor("weights", &[record.outputs, record.inputs])?;
+                let mut inputs = vec![self.initializer(
+                    format!("{}.weights", prefix),
+                    weights.shape(),
+                    weights.data(),
+                )];
+                if record.tensor("biases").is_some() {
+                    let biases = record.expect_tensor("biases", &[record.outputs])?;
+                    inputs.push(self.initializer(
+                        format!("{}.biases", prefix),
+                        biases.shape(),
+                        biases.data(),
+                    ));
+                }
+                self.node("Gemm", &inputs, &[("transB", 1)]);
+                match record.activation.as_deref() {
+                    Some("linear") => {}
+                    Some("relu") => self.node("Relu", &[], &[]),
+                    Some("sigmoid") => self.node("Sigmoid", &[], &[]),
+                    Some("tanh") => self.node("Tanh", &[], &[]),
+                    other => {
+                        return Err(ModelError::Unsupported(format!(
+                            "activation {:?} has no ONNX equivalent",
+                            other
+                        )))
+                    }
+                }
+            }
+            "softmax" => self.node("Softmax", &[], &[("axis", 1)]),
+            kind => {
+                return Err(ModelError::Unsupported(format!(
+                    "{} layers can't be exported to ONNX",
+                    kind
+                )))
+            }
+        }
+        Ok(())
+    }
+}
+
+fn value_info(name: &str, size: usize) -> Message {
+    let mut batch = Message::default();
+    batch.string(2, "batch");
+    let mut features = Message::default();
+    features.uint(1, size as u64);
+    let mut shape = Message::default();
+    shape.message(1, &batch).message(1, &features);
+    let mut tensor_type = Message::default();
+    tensor_type.uint(1, FLOAT).message(2, &shape);
+    let mut type_proto = Message::default();
+    type_proto.message(1, &tensor_type);
+    let mut info = Message::default();
+    info.string(1, name).message(2, &type_proto);
+    info
+}
+
+/// Encodes `record` as an ONNX `ModelProto`.
+pub fn to_bytes(record: &LayerRecord) -> Result<Vec<u8>, ModelError> {
+    let mut graph = Graph {
+        nodes: vec![],
+        initializers: vec![],
+        last_output: "input".to_string(),
+    };
+    graph.add_layer(record)?;
+    if graph.nodes.is_empty() {
+        return Err(ModelError::Unsupported(
+            "can't export a model without layers".to_string(),
+        ));
+    }
+    // the output name is fixed, so whatever the last layer was it ends with
+    // an Identity writing to `output`
+    graph.node_to("Identity", &[], &[], "output".to_string());
+
+    let mut proto = Message::default();
+    for node in graph.nodes.iter() {
+        proto.message(1, node);
+    }
+    proto.string(2, "rann");
+    for initializer in graph.initializers.iter() {
+        proto.message(5, initializer);
+    }
+    proto
+        .message(11, &value_info("input", record.inputs))
+        .message(12, &value_info("output", record.outputs));
+
+    let mut opset = Message::default();
+    opset.string(1, "").uint(2, OPSET_VERSION);
+    let mut model = Message::default();
+    model
+        .uint(1, IR_VERSION)
+        .string(2, "rann")
+        .string(3, env!("CARGO_PKG_VERSION"))
+        .message(7, &proto)
+        .message(8, &opset);
+    Ok(model.bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::activations::Activation::*;
+    use crate::node::RannV2;
+    use crate::rann::Rann;
+    use crate::testing::temp;
+    use std::collections::HashMap;
+
+    enum Value<'a> {
+        Varint(u64),
+        Bytes(&'a [u8]),
+    }
+
+    fn varint(bytes: &[u8], position: &mut usize) -> u64 {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = bytes[*position];
+            *position += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte < 0x80 {
+                return value;
+            }
+            shift += 7;
+        }
+    }
+
+    // A minimal protobuf reader, enough for the wire types the exporter uses.
+    fn parse(bytes: &[u8]) -> Vec<(u64, Value<'_>)> {
+        let mut fields = vec![];
+        let mut position = 0;
+        while position < bytes.len() {
+            let key = varint(bytes, &mut position);
+            let value = match key & 7 {
+                0 => Value::Varint(varint(bytes, &mut position)),
+                2 => {
+                    let length = varint(bytes, &mut position) as usize;
+                    position += length;
+                    Value::Bytes(&bytes[position - length..position])
+                }
+                wire_type => panic!("unexpected wire type {}", wire_type),
+            };
+            fields.push((key >> 3, value));
+        }
+        fields
+    }
+
+    fn uints(bytes: &[u8], field: u64) -> Vec<u64> {
+        parse(bytes)
+            .into_iter()
+            .filter_map(|(f, value)| match value {
+                Value::Varint(v) if f == field => Some(v),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn messages(bytes: &[u8], field: u64) -> Vec<&[u8]> {
+        parse(bytes)
+            .into_iter()
+            .filter_map(|(f, value)| match value {
+                Value::Bytes(b) if f == field => Some(b),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn strings(bytes: &[u8], field: u64) -> Vec<String> {
+        messages(bytes, field)
+            .into_iter()
+            .map(|b| String::from_utf8(b.to_vec()).unwrap())
+            .collect()
+    }
+
+    /// Runs the exported graph on a single input row.
+    fn run(model: &[u8], input: &[f32]) -> Vec<f32> {
+        assert_eq!(uints(model, 1), vec![IR_VERSION]);
+        assert_eq!(uints(messages(model, 8)[0], 2), vec![OPSET_VERSION]);
+        let graph = messages(model, 7)[0];
+
+        let mut values: HashMap<String, (Vec<usize>, Vec<f32>)> = HashMap::new();
+        for tensor in messages(graph, 5) {
+            assert_eq!(uints(tensor, 2), vec![FLOAT]);
+            let dims = uints(tensor, 1).iter().map(|d| *d as usize).collect();
+            let data = messages(tensor, 9)[0]
+                .chunks(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+            values.insert(strings(tensor, 8)[0].clone(), (dims, data));
+        }
+        let input_name = strings(messages(graph, 11)[0], 1)[0].clone();
+        values.insert(input_name, (vec![1, input.len()], input.to_vec()));
+
+        for node in messages(graph, 1) {
+            let inputs = strings(node, 1);
+            let op_type = &strings(node, 4)[0];
+            let (_, x) = values[&inputs[0]].clone();
+            let y: Vec<f32> = match op_type.as_str() {
+                "Gemm" => {
+                    let attribute = messages(node, 5)[0];
+                    assert_eq!(strings(attribute, 1)[0], "transB");
+                    assert_eq!(uints(attribute, 3), vec![1]);
+                    let (dims, w) = &values[&inputs[1]];
+                    (0..dims[0])
+                        .map(|o| {
+                            let bias = inputs.get(2).map_or(0., |name| values[name].1[o]);
+                            let row = &w[o * dims[1]..(o + 1) * dims[1]];
+                            row.iter().zip(&x).map(|(a, b)| a * b).sum::<f32>() + bias
+                        })
+                        .collect()
+                }
+                "Relu" => x.iter().map(|v| v.max(0.)).collect(),
+                "Sigmoid" => x.iter().map(|v| 1. / (1. + (-v).exp())).collect(),
+                "Tanh" => x.iter().map(|v| v.tanh()).collect(),
+                "Softmax" => {
+                    let max = x.iter().cloned().fold(f32::MIN, f32::max);
+                    let sum: f32 = x.iter().map(|v| (v - max).exp()).sum();
+                    x.iter().map(|v| (v - max).exp() / sum).collect()
+                }
+                "Identity" => x,
+                op => panic!("unexpected op {}", op),
+            };
+            let output = strings(node, 2)[0].clone();
+            values.insert(output, (vec![1, y.len()], y));
+        }
+        let output_name = strings(messages(graph, 12)[0], 1)[0].clone();
+        values[&output_name].1.clone()
+    }
+
+    fn assert_close(a: &[f32], b: &[f32]) {
+        assert_eq!(a.len(), b.len());
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).abs() < 1e-5, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn onnx_rann_round_trip() {
+        let path = temp("onnx.onnx");
+        let mut rann = Rann::with_activations(&[
+            (4, Linear),
+            (5, Relu),
+            (3, Tanh),
+            (3, Sigmoid),
+            (2, Softmax),
+        ]);
+        rann.export_onnx(&path).unwrap();
+        let model = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        for input in [[0.5, -1., 2., 0.1], [-3., 0.2, 0.7, 1.5]].iter() {
+            assert_close(&run(&model, input), &rann.forward(input));
+        }
+    }
+
+    #[test]
+    fn onnx_rannv2_round_trip() {
+        let path = temp("rannv2_onnx.onnx");
+        let mut rannv2 = RannV2::new(vec![3, 4, 3]);
+        rannv2.export_onnx(&path).unwrap();
+        let model = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let input = [0.3, -0.6, 1.2];
+        assert_close(&run(&model, &input), &rannv2.forward(&input));
+    }
+
+    #[test]
+    fn onnx_unsupported() {
+        let mut record = LayerRecord::new("dense", 1, 1);
+        record.activation = Some("swish".to_string());
+        record
+            .tensors
+            .push(("weights".to_string(), crate::tensor::Tensor::zeros(&[1, 1])));
+        match to_bytes(&record) {
+            Err(ModelError::Unsupported(_)) => {}
+            _ => panic!("expected an unsupported activation"),
+        }
+        assert!(to_bytes(&LayerRecord::new("sequential", 0, 0)).is_err());
+    }
+}
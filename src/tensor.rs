@@ -0,0 +1,481 @@
+use std::cmp::Ordering;
+use std::ops::{Add, Div, Mul, Sub};
+
+/// A dense, row-major n-dimensional array of `f32`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tensor {
+    data: Vec<f32>,
+    shape: Vec<usize>,
+    strides: Vec<usize>,
+}
+
+fn strides_for(shape: &[usize]) -> Vec<usize> {
+    let mut strides = vec![1; shape.len()];
+    for i in (0..shape.len().saturating_sub(1)).rev() {
+        strides[i] = strides[i + 1] * shape[i + 1];
+    }
+    strides
+}
+
+// Shape two tensors broadcast to, following the numpy rules.
+fn broadcast_shape(a: &[usize], b: &[usize]) -> Vec<usize> {
+    let rank = a.len().max(b.len());
+    (0..rank)
+        .map(|i| {
+            let a_dim = if i + a.len() >= rank {
+                a[i + a.len() - rank]
+            } else {
+                1
+            };
+            let b_dim = if i + b.len() >= rank {
+                b[i + b.len() - rank]
+            } else {
+                1
+            };
+            match (a_dim, b_dim) {
+                (x, y) if x == y => x,
+                (1, y) => y,
+                (x, 1) => x,
+                _ => panic!("cannot broadcast shapes {:?} and {:?}", a, b),
+            }
+        })
+        .collect()
+}
+
+impl Tensor {
+    pub fn new(data: Vec<f32>, shape: &[usize]) -> Self {
+        assert_eq!(
+            data.len(),
+            shape.iter().product::<usize>(),
+            "data does not fit shape {:?}",
+            shape
+        );
+        Self {
+            data,
+            shape: shape.to_vec(),
+            strides: strides_for(shape),
+        }
+    }
+
+    pub fn zeros(shape: &[usize]) -> Self {
+        Self::filled(shape, 0.)
+    }
+
+    pub fn filled(shape: &[usize], value: f32) -> Self {
+        Self::new(vec![value; shape.iter().product()], shape)
+    }
+
+    pub fn from_fn<F: FnMut(usize) -> f32>(shape: &[usize], f: F) -> Self {
+        Self::new((0..shape.iter().product()).map(f).collect(), shape)
+    }
+
+    pub fn vector(data: &[f32]) -> Self {
+        Self::new(data.to_vec(), &[data.len()])
+    }
+
+    pub fn from_rows(rows: &[Vec<f32>]) -> Self {
+        let columns = rows.first().map(|row| row.len()).unwrap_or(0);
+        assert!(rows.iter().all(|row| row.len() == columns));
+        Self::new(rows.concat(), &[rows.len(), columns])
+    }
+
+    /// Outer product of two vectors, shaped `[a.len(), b.len()]`.
+    pub fn outer(a: &[f32], b: &[f32]) -> Self {
+        let mut data = Vec::with_capacity(a.len() * b.len());
+        for x in a {
+            data.extend(b.iter().map(|y| x * y));
+        }
+        Self::new(data, &[a.len(), b.len()])
+    }
+
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    pub fn strides(&self) -> &[usize] {
+        &self.strides
+    }
+
+    pub fn rank(&self) -> usize {
+        self.shape.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn data(&self) -> &[f32] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [f32] {
+        &mut self.data
+    }
+
+    pub fn into_data(self) -> Vec<f32> {
+        self.data
+    }
+
+    fn offset(&self, index: &[usize]) -> usize {
+        assert_eq!(index.len(), self.rank());
+        index
+            .iter()
+            .zip(&self.shape)
+            .zip(&self.strides)
+            .map(|((i, dim), stride)| {
+                assert!(
+                    i < dim,
+                    "index {:?} out of bounds for {:?}",
+                    index,
+                    self.shape
+                );
+                i * stride
+            })
+            .sum()
+    }
+
+    pub fn get(&self, index: &[usize]) -> f32 {
+        self.data[self.offset(index)]
+    }
+
+    pub fn set(&mut self, index: &[usize], value: f32) {
+        let offset = self.offset(index);
+        self.data[offset] = value;
+    }
+
+    /// Row `i` of a matrix.
+    pub fn row(&self, i: usize) -> &[f32] {
+        assert_eq!(self.rank(), 2);
+        let columns = self.shape[1];
+        &self.data[i * columns..(i + 1) * columns]
+    }
+
+    pub fn row_mut(&mut self, i: usize) -> &mut [f32] {
+        assert_eq!(self.rank(), 2);
+        let columns = self.shape[1];
+        &mut self.data[i * columns..(i + 1) * columns]
+    }
+
+    pub fn rows(&self) -> Vec<Vec<f32>> {
+        assert_eq!(self.rank(), 2);
+        self.data
+            .chunks(self.shape[1].max(1))
+            .map(|row| row.to_vec())
+            .collect()
+    }
+
+    pub fn reshape(mut self, shape: &[usize]) -> Self {
+        assert_eq!(self.len(), shape.iter().product::<usize>());
+        self.strides = strides_for(shape);
+        self.shape = shape.to_vec();
+        self
+    }
+
+    /// Swaps the two axes of a matrix.
+    pub fn transpose(&self) -> Self {
+        assert_eq!(self.rank(), 2);
+        let (rows, columns) = (self.shape[0], self.shape[1]);
+        let mut data = vec![0.; self.len()];
+        for i in 0..rows {
+            for j in 0..columns {
+                data[j * rows + i] = self.data[i * columns + j];
+            }
+        }
+        Self::new(data, &[columns, rows])
+    }
+
+    /// Matrix product. A vector on the left is treated as a row and a vector
+    /// on the right as a column, and that dimension is dropped again from the
+    /// result.
+    pub fn matmul(&self, other: &Tensor) -> Self {
+        assert!(self.rank() == 1 || self.rank() == 2);
+        assert!(other.rank() == 1 || other.rank() == 2);
+        let (rows, inner) = match self.rank() {
+            1 => (1, self.shape[0]),
+            _ => (self.shape[0], self.shape[1]),
+        };
+        let columns = match other.rank() {
+            1 => 1,
+            _ => other.shape[1],
+        };
+        assert_eq!(
+            inner, other.shape[0],
+            "cannot multiply {:?} by {:?}",
+            self.shape, other.shape
+        );
+
+        let mut data = vec![0.; rows * columns];
+        for i in 0..rows {
+            let out = &mut data[i * columns..(i + 1) * columns];
+            for k in 0..inner {
+                let a = self.data[i * inner + k];
+                let b = &other.data[k * columns..(k + 1) * columns];
+                for (o, b) in out.iter_mut().zip(b) {
+                    *o += a * b;
+                }
+            }
+        }
+
+        let shape = match (self.rank(), other.rank()) {
+            (1, 1) => vec![],
+            (1, _) => vec![columns],
+            (_, 1) => vec![rows],
+            _ => vec![rows, columns],
+        };
+        Self::new(data, &shape)
+    }
+
+    pub fn map<F: Fn(f32) -> f32>(&self, f: F) -> Self {
+        Self {
+            data: self.data.iter().map(|x| f(*x)).collect(),
+            shape: self.shape.clone(),
+            strides: self.strides.clone(),
+        }
+    }
+
+    pub fn scale(&self, factor: f32) -> Self {
+        self.map(|x| x * factor)
+    }
+
+    /// Applies `f` element-wise after broadcasting both tensors to a common
+    /// shape.
+    pub fn zip_with<F: Fn(f32, f32) -> f32>(&self, other: &Tensor, f: F) -> Self {
+        if self.shape == other.shape {
+            let data = self
+                .data
+                .iter()
+                .zip(&other.data)
+                .map(|(a, b)| f(*a, *b))
+                .collect();
+            return Self::new(data, &self.shape);
+        }
+
+        let shape = broadcast_shape(&self.shape, &other.shape);
+        let a_strides = self.broadcast_strides(&shape);
+        let b_strides = other.broadcast_strides(&shape);
+        let mut index = vec![0; shape.len()];
+        let mut data = Vec::with_capacity(shape.iter().product());
+        for _ in 0..shape.iter().product::<usize>() {
+            let a: usize = index.iter().zip(&a_strides).map(|(i, s)| i * s).sum();
+            let b: usize = index.iter().zip(&b_strides).map(|(i, s)| i * s).sum();
+            data.push(f(self.data[a], other.data[b]));
+            for axis in (0..shape.len()).rev() {
+                index[axis] += 1;
+                if index[axis] < shape[axis] {
+                    break;
+                }
+                index[axis] = 0;
+            }
+        }
+        Self::new(data, &shape)
+    }
+
+    // Strides that read this tensor as if it had `shape`; broadcast axes get a
+    // stride of zero.
+    fn broadcast_strides(&self, shape: &[usize]) -> Vec<usize> {
+        let padding = shape.len() - self.rank();
+        (0..shape.len())
+            .map(|i| {
+                if i < padding || self.shape[i - padding] == 1 {
+                    0
+                } else {
+                    self.strides[i - padding]
+                }
+            })
+            .collect()
+    }
+
+    /// Element-wise `self += other` without allocating. Shapes must match.
+    pub fn add_assign(&mut self, other: &Tensor) {
+        assert_eq!(self.shape, other.shape);
+        for (a, b) in self.data.iter_mut().zip(&other.data) {
+            *a += b;
+        }
+    }
+
+    pub fn fill(&mut self, value: f32) {
+        for x in self.data.iter_mut() {
+            *x = value;
+        }
+    }
+
+    pub fn sum(&self) -> f32 {
+        self.data.iter().sum()
+    }
+
+    pub fn mean(&self) -> f32 {
+        if self.is_empty() {
+            return 0.;
+        }
+        self.sum() / self.len() as f32
+    }
+
+    pub fn max(&self) -> f32 {
+        self.data.iter().cloned().fold(f32::NEG_INFINITY, f32::max)
+    }
+
+    pub fn min(&self) -> f32 {
+        self.data.iter().cloned().fold(f32::INFINITY, f32::min)
+    }
+
+    /// Index of the largest element in the flattened data.
+    pub fn argmax(&self) -> usize {
+        self.data
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+            .map(|(i, _)| i)
+            .unwrap_or(0)
+    }
+
+    /// Sums over `axis`, removing it from the shape.
+    pub fn sum_axis(&self, axis: usize) -> Self {
+        assert!(axis < self.rank());
+        let mut shape = self.shape.clone();
+        shape.remove(axis);
+        let outer: usize = self.shape[..axis].iter().product();
+        let dim = self.shape[axis];
+        let inner = self.strides[axis];
+        let mut data = vec![0.; outer * inner];
+        for o in 0..outer {
+            for d in 0..dim {
+                let start = (o * dim + d) * inner;
+                for (out, x) in data[o * inner..(o + 1) * inner]
+                    .iter_mut()
+                    .zip(&self.data[start..start + inner])
+                {
+                    *out += x;
+                }
+            }
+        }
+        Self::new(data, &shape)
+    }
+}
+
+impl<'a> Add<&'a Tensor> for &'a Tensor {
+    type Output = Tensor;
+
+    fn add(self, other: &Tensor) -> Tensor {
+        self.zip_with(other, |a, b| a + b)
+    }
+}
+
+impl<'a> Sub<&'a Tensor> for &'a Tensor {
+    type Output = Tensor;
+
+    fn sub(self, other: &Tensor) -> Tensor {
+        self.zip_with(other, |a, b| a - b)
+    }
+}
+
+impl<'a> Mul<&'a Tensor> for &'a Tensor {
+    type Output = Tensor;
+
+    fn mul(self, other: &Tensor) -> Tensor {
+        self.zip_with(other, |a, b| a * b)
+    }
+}
+
+impl<'a> Div<&'a Tensor> for &'a Tensor {
+    type Output = Tensor;
+
+    fn div(self, other: &Tensor) -> Tensor {
+        self.zip_with(other, |a, b| a / b)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shape_and_strides() {
+        let t = Tensor::zeros(&[2, 3, 4]);
+        assert_eq!(t.shape(), &[2, 3, 4]);
+        assert_eq!(t.strides(), &[12, 4, 1]);
+        assert_eq!(t.len(), 24);
+
+        let mut t = Tensor::new(vec![1., 2., 3., 4., 5., 6.], &[2, 3]);
+        assert_eq!(t.get(&[1, 0]), 4.);
+        t.set(&[0, 2], 9.);
+        assert_eq!(t.row(0), &[1., 2., 9.]);
+        let t = t.reshape(&[3, 2]);
+        assert_eq!(t.get(&[1, 0]), 9.);
+    }
+
+    #[test]
+    #[should_panic]
+    fn wrong_shape() {
+        Tensor::new(vec![1., 2., 3.], &[2, 2]);
+    }
+
+    #[test]
+    fn vec_mult_mat() {
+        let res =
+            Tensor::from_rows(&[vec![4.], vec![6.]]).matmul(&Tensor::from_rows(&[vec![-2., 5.]]));
+        assert_eq!(res.row(0), &[-8., 20.]);
+        assert_eq!(res.row(1), &[-12., 30.]);
+
+        let res = Tensor::from_rows(&[vec![4., -2., 2.], vec![1., 5., -9.]]).matmul(
+            &Tensor::from_rows(&[vec![12., -5.], vec![-3., -2.], vec![4., 2.]]),
+        );
+        assert_eq!(res.row(0), &[62., -12.]);
+        assert_eq!(res.row(1), &[-39., -33.]);
+    }
+
+    #[test]
+    fn matmul_vectors() {
+        let mat = Tensor::new(vec![1., 2., 3., 4., 5., 6.], &[2, 3]);
+        let res = mat.matmul(&Tensor::vector(&[1., 0., -1.]));
+        assert_eq!(res.shape(), &[2]);
+        assert_eq!(res.data(), &[-2., -2.]);
+
+        let res = Tensor::vector(&[1., -1.]).matmul(&mat);
+        assert_eq!(res.shape(), &[3]);
+        assert_eq!(res.data(), &[-3., -3., -3.]);
+
+        let res = Tensor::vector(&[1., 2.]).matmul(&Tensor::vector(&[3., 4.]));
+        assert_eq!(res.data(), &[11.]);
+    }
+
+    #[test]
+    fn transpose() {
+        let t = Tensor::new(vec![1., 2., 3., 4., 5., 6.], &[2, 3]).transpose();
+        assert_eq!(t.shape(), &[3, 2]);
+        assert_eq!(t.data(), &[1., 4., 2., 5., 3., 6.]);
+    }
+
+    #[test]
+    fn element_wise_and_broadcasting() {
+        let a = Tensor::new(vec![1., 2., 3., 4., 5., 6.], &[2, 3]);
+        let b = Tensor::new(vec![1., 1., 1., 2., 2., 2.], &[2, 3]);
+        assert_eq!((&a - &b).data(), &[0., 1., 2., 2., 3., 4.]);
+        assert_eq!((&a * &b).data(), &[1., 2., 3., 8., 10., 12.]);
+
+        let row = Tensor::vector(&[10., 20., 30.]);
+        assert_eq!((&a + &row).data(), &[11., 22., 33., 14., 25., 36.]);
+
+        let column = Tensor::new(vec![1., 2.], &[2, 1]);
+        assert_eq!((&a / &column).data(), &[1., 2., 3., 2., 2.5, 3.]);
+
+        let outer = &column * &row;
+        assert_eq!(outer.shape(), &[2, 3]);
+        assert_eq!(outer, Tensor::outer(&[1., 2.], &[10., 20., 30.]));
+    }
+
+    #[test]
+    fn reductions() {
+        let t = Tensor::new(vec![1., 5., 3., -4., 2., 6.], &[2, 3]);
+        assert_eq!(t.sum(), 13.);
+        assert_eq!(t.max(), 6.);
+        assert_eq!(t.min(), -4.);
+        assert_eq!(t.argmax(), 5);
+        assert_eq!(t.sum_axis(0).data(), &[-3., 7., 9.]);
+        assert_eq!(t.sum_axis(1).data(), &[9., 4.]);
+        assert!((t.mean() - 13. / 6.).abs() < 1e-6);
+    }
+}
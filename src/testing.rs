@@ -0,0 +1,17 @@
+//! Fixtures shared by the unit tests.
+
+use std::path::PathBuf;
+
+/// A path named after `name` in the temporary directory, unique to the test
+/// process so that parallel runs don't clash. Tests remove what they write.
+pub fn temp(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("rann_{}_{}", std::process::id(), name))
+}
+
+pub fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-5
+}
+
+pub fn all_close(a: &[f32], b: &[f32]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(x, y)| close(*x, *y))
+}
@@ -0,0 +1,266 @@
+use crate::callbacks::Callback;
+use crate::data::split::{Fold, KFold};
+use crate::data::Dataset;
+use crate::metrics::Metric;
+use crate::rann::Rann;
+use crate::schedulers::LrScheduler;
+use std::fmt;
+use std::mem;
+
+/// Where `fit` takes the samples it validates on after every epoch.
+pub enum Validation<'a> {
+    None,
+    /// Holds out this fraction of the training data, taken from its end
+    /// before any shuffling.
+    Split(f32),
+    Data(&'a dyn Dataset),
+}
+
+pub struct FitConfig<'a> {
+    pub epochs: usize,
+    pub batch_size: usize,
+    /// Reorders the training samples before every epoch.
+    pub shuffle: bool,
+    pub validation: Validation<'a>,
+    /// Computed on the training and validation data after every epoch.
+    pub metrics: Vec<Box<dyn Metric>>,
+    /// Prints a line per epoch.
+    pub verbose: bool,
+    /// Called in order at every batch and epoch boundary.
+    pub callbacks: Vec<&'a mut dyn Callback>,
+    /// Sets the learning rate before every batch.
+    pub scheduler: Option<Box<dyn LrScheduler>>,
+}
+
+impl Default for FitConfig<'_> {
+    fn default() -> Self {
+        Self {
+            epochs: 1,
+            batch_size: 32,
+            shuffle: true,
+            validation: Validation::None,
+            metrics: vec![],
+            verbose: false,
+            callbacks: vec![],
+            scheduler: None,
+        }
+    }
+}
+
+/// Mean loss and metric values over a dataset.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scores {
+    pub loss: f32,
+    pub metrics: Vec<(String, f32)>,
+}
+
+impl Scores {
+    pub fn metric(&self, name: &str) -> Option<f32> {
+        self.metrics
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| *value)
+    }
+}
+
+impl fmt::Display for Scores {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "loss {:.4}", self.loss)?;
+        for (name, value) in self.metrics.iter() {
+            write!(f, ", {} {:.4}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EpochLog {
+    pub epoch: usize,
+    /// Loss averaged over the batches of the epoch, metrics over the
+    /// predictions made while training.
+    pub train: Scores,
+    pub validation: Option<Scores>,
+    /// The learning rate of the last batch of the epoch.
+    pub learning_rate: f32,
+}
+
+impl fmt::Display for EpochLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "epoch {}: {}", self.epoch + 1, self.train)?;
+        if let Some(validation) = &self.validation {
+            write!(f, " | validation {}", validation)?;
+        }
+        Ok(())
+    }
+}
+
+/// What `fit` returns: one `EpochLog` per epoch that was run.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct History {
+    pub epochs: Vec<EpochLog>,
+}
+
+impl History {
+    pub fn loss(&self) -> Vec<f32> {
+        self.epochs.iter().map(|log| log.train.loss).collect()
+    }
+
+    pub fn val_loss(&self) -> Vec<f32> {
+        self.epochs
+            .iter()
+            .filter_map(|log| log.validation.as_ref())
+            .map(|scores| scores.loss)
+            .collect()
+    }
+
+    pub fn metric(&self, name: &str) -> Vec<f32> {
+        self.epochs
+            .iter()
+            .filter_map(|log| log.train.metric(name))
+            .collect()
+    }
+
+    pub fn learning_rate(&self) -> Vec<f32> {
+        self.epochs.iter().map(|log| log.learning_rate).collect()
+    }
+
+    pub fn val_metric(&self, name: &str) -> Vec<f32> {
+        self.epochs
+            .iter()
+            .filter_map(|log| log.validation.as_ref())
+            .filter_map(|scores| scores.metric(name))
+            .collect()
+    }
+}
+
+/// The scores of every fold of a cross-validation.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CrossValidation {
+    /// Loss and metrics of the model of each fold on the samples it held out.
+    pub folds: Vec<Scores>,
+    pub histories: Vec<History>,
+}
+
+impl CrossValidation {
+    pub fn loss(&self) -> Vec<f32> {
+        self.folds.iter().map(|scores| scores.loss).collect()
+    }
+
+    pub fn metric(&self, name: &str) -> Vec<f32> {
+        self.folds
+            .iter()
+            .filter_map(|scores| scores.metric(name))
+            .collect()
+    }
+
+    /// Loss and metrics averaged over the folds.
+    pub fn mean(&self) -> Scores {
+        let count = self.folds.len().max(1) as f32;
+        let names = self.folds.first().map_or(vec![], |scores| {
+            scores
+                .metrics
+                .iter()
+                .map(|(name, _)| name.clone())
+                .collect()
+        });
+        Scores {
+            loss: self.loss().iter().sum::<f32>() / count,
+            metrics: names
+                .into_iter()
+                .map(|name| {
+                    let mean = self.metric(&name).iter().sum::<f32>() / count;
+                    (name, mean)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// K-fold cross-validation over consecutive folds of `dataset`; see
+/// `cross_validate_folds`.
+pub fn cross_validate<'a, D, M>(
+    model_factory: impl FnMut() -> M,
+    dataset: &D,
+    k: usize,
+    config: impl FnMut() -> FitConfig<'a>,
+) -> CrossValidation
+where
+    D: Dataset + ?Sized,
+    M: Into<Rann>,
+{
+    cross_validate_folds(model_factory, KFold::new(dataset, k), config)
+}
+
+/// Fits a fresh model from `model_factory` on the training part of every
+/// fold and scores it on the held-out part. The metrics of the `FitConfig`
+/// are only computed on the held-out part, once training is done.
+pub fn cross_validate_folds<'a, 'd, D, M>(
+    mut model_factory: impl FnMut() -> M,
+    folds: impl IntoIterator<Item = Fold<'d, D>>,
+    mut config: impl FnMut() -> FitConfig<'a>,
+) -> CrossValidation
+where
+    D: Dataset + ?Sized + 'd,
+    M: Into<Rann>,
+{
+    let mut result = CrossValidation::default();
+    for fold in folds {
+        let mut model: Rann = model_factory().into();
+        let mut config = config();
+        let metrics = mem::take(&mut config.metrics);
+        result.histories.push(model.fit(&fold.train, config));
+        result
+            .folds
+            .push(model.evaluate(&fold.validation, &metrics));
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::Generated;
+    use crate::metrics::Accuracy;
+    use crate::node::RannV2;
+    use crate::optimizers::Adam;
+
+    #[test]
+    fn cross_validation() {
+        // class 1 above the diagonal, class 0 below
+        let data = Generated::new(60, |i| {
+            let (x, y) = ((i % 10) as f32 / 10., (i * 7 % 11) as f32 / 11.);
+            let target = if y > x { vec![0., 1.] } else { vec![1., 0.] };
+            (vec![x, y], target)
+        });
+        let mut seed = 0;
+        let result = cross_validate_folds(
+            || {
+                seed += 1;
+                RannV2::new(vec![2, 8, 2])
+                    .with_optimizer(Adam::new(0.05))
+                    .with_seed(seed)
+            },
+            KFold::new(&data, 3).stratified().with_seed(1),
+            || FitConfig {
+                epochs: 60,
+                batch_size: 4,
+                metrics: vec![Box::new(Accuracy::new())],
+                ..FitConfig::default()
+            },
+        );
+        assert_eq!(result.folds.len(), 3);
+        assert_eq!(result.histories[0].epochs.len(), 60);
+        assert_eq!(result.metric("accuracy").len(), 3);
+        let mean = result.mean();
+        assert!(mean.metric("accuracy").unwrap() > 0.8, "{}", mean);
+        assert!((mean.loss - result.loss().iter().sum::<f32>() / 3.).abs() < 1e-6);
+
+        let result = cross_validate(
+            || RannV2::new(vec![2, 4, 2]).with_seed(0),
+            &data,
+            4,
+            FitConfig::default,
+        );
+        assert_eq!(result.folds.len(), 4);
+    }
+}